@@ -0,0 +1,104 @@
+/*
+ * libchip8, the chip8 emulator core as a C library.
+ *
+ * A Chip8 is an opaque handle from chip8_create, freed with chip8_destroy.
+ * Every function taking one needs a live handle, and pointers to buffers
+ * must be valid for the length passed with them. Functions returning int
+ * give CHIP8_OK or CHIP8_ERROR, after an error chip8_last_error describes
+ * it. A machine that crashes keeps failing until a ROM or save state is
+ * loaded.
+ *
+ * chip8_create takes a quirks profile, "vip", "chip48", "schip" or "octo",
+ * or NULL for the default, and the seed for Cxkk. It returns NULL for an
+ * unknown profile.
+ *
+ * chip8_framebuffer points at CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT
+ * bytes of 0 or 1, row by row, updated by every call that runs the machine
+ * and valid for the life of the handle.
+ *
+ * chip8_set_key holds a key 0 to 0xf down until the next call, 0xff
+ * releases it. chip8_step runs one instruction, chip8_run_frame runs to the
+ * end of the frame, both tick the timers when a frame ends. An instruction
+ * that isn't one or reaches outside memory, the display or the stack is an
+ * error rather than a crash of the process.
+ *
+ * chip8_set_registers fails without changing anything when pc or i is past
+ * 0xfff or sp is outside the stack at 0xfa0-0xffe.
+ *
+ * Save states are chip8_state_size() bytes, the same format the chip8
+ * binary writes.
+ *
+ * New functions bump CHIP8_API_VERSION, existing ones and the layout of
+ * Chip8Registers don't change.
+ */
+
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from src/capi.rs, don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define CHIP8_API_VERSION 1
+
+#define CHIP8_OK 0
+
+#define CHIP8_ERROR -1
+
+#define CHIP8_DISPLAY_WIDTH 64
+
+#define CHIP8_DISPLAY_HEIGHT 32
+
+#define CHIP8_MAX_ROM_SIZE (4096 - 512)
+
+typedef struct Chip8 Chip8;
+
+typedef struct Chip8Registers {
+  uint8_t v[16];
+  uint8_t dt;
+  uint8_t st;
+  uint16_t i;
+  uint16_t sp;
+  uint16_t pc;
+} Chip8Registers;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+uint32_t chip8_api_version(void);
+
+Chip8 *chip8_create(const char *quirks, uint64_t seed);
+
+void chip8_destroy(Chip8 *chip8);
+
+const char *chip8_last_error(const Chip8 *chip8);
+
+int chip8_load_rom(Chip8 *chip8, const uint8_t *rom, size_t len);
+
+void chip8_set_key(Chip8 *chip8, uint8_t key);
+
+int chip8_step(Chip8 *chip8);
+
+int chip8_run_frame(Chip8 *chip8);
+
+const uint8_t *chip8_framebuffer(const Chip8 *chip8);
+
+void chip8_get_registers(const Chip8 *chip8, Chip8Registers *registers);
+
+int chip8_set_registers(Chip8 *chip8, const Chip8Registers *registers);
+
+size_t chip8_state_size(void);
+
+int chip8_save_state(Chip8 *chip8, uint8_t *buffer, size_t len);
+
+int chip8_load_state(Chip8 *chip8, const uint8_t *state, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,179 @@
+/*
+ * Exercises every function in chip8.h against small ROMs built in place.
+ * Run with make -C capi, exits nonzero and says which check failed if any
+ * does.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "chip8.h"
+
+static int failures = 0;
+
+#define CHECK(cond) do { \
+    if (!(cond)) { \
+        fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+        failures++; \
+    } \
+} while (0)
+
+/* draws the 0 glyph at 0,0 then waits for key 5 and sets V2 to 0x0f */
+static const uint8_t KEY_ROM[] = {
+    0x60, 0x00, /* 200 V0 = 0 */
+    0xf0, 0x29, /* 202 I = glyph for V0 */
+    0xd0, 0x05, /* 204 draw it at V0, V0 */
+    0x61, 0x05, /* 206 V1 = 5 */
+    0xe1, 0x9e, /* 208 skip next if key V1 is down */
+    0x12, 0x08, /* 20a jump 208 */
+    0x62, 0x0f, /* 20c V2 = 0x0f */
+    0x12, 0x0e, /* 20e jump 20e */
+};
+
+/* calls itself until the stack runs off the end of memory */
+static const uint8_t CRASH_ROM[] = {0x22, 0x00};
+
+static void test_create(void)
+{
+    CHECK(chip8_api_version() == CHIP8_API_VERSION);
+    CHECK(chip8_create("no such profile", 0) == NULL);
+
+    Chip8 *chip8 = chip8_create("vip", 0);
+    CHECK(chip8 != NULL);
+    chip8_destroy(chip8);
+    chip8_destroy(NULL);
+}
+
+static void test_step_and_keys(void)
+{
+    Chip8 *chip8 = chip8_create(NULL, 1);
+    Chip8Registers reg;
+
+    CHECK(chip8_load_rom(chip8, KEY_ROM, sizeof KEY_ROM) == CHIP8_OK);
+    chip8_get_registers(chip8, &reg);
+    CHECK(reg.pc == 0x200);
+
+    for (int i = 0; i < 3; i++)
+        CHECK(chip8_step(chip8) == CHIP8_OK);
+    chip8_get_registers(chip8, &reg);
+    CHECK(reg.pc == 0x206);
+
+    /* the top row of the 0 glyph is 0xf0 */
+    const uint8_t *pixels = chip8_framebuffer(chip8);
+    const uint8_t top_row[8] = {1, 1, 1, 1, 0, 0, 0, 0};
+    CHECK(memcmp(pixels, top_row, sizeof top_row) == 0);
+    CHECK(pixels[CHIP8_DISPLAY_WIDTH * 5] == 0);
+
+    CHECK(chip8_run_frame(chip8) == CHIP8_OK);
+    chip8_get_registers(chip8, &reg);
+    CHECK(reg.pc == 0x208 || reg.pc == 0x20a);
+    CHECK(reg.v[2] == 0);
+
+    chip8_set_key(chip8, 5);
+    CHECK(chip8_run_frame(chip8) == CHIP8_OK);
+    chip8_get_registers(chip8, &reg);
+    CHECK(reg.pc == 0x20e);
+    CHECK(reg.v[2] == 0x0f);
+
+    /* one frame takes one off the delay timer */
+    reg.dt = 10;
+    CHECK(chip8_set_registers(chip8, &reg) == CHIP8_OK);
+    CHECK(chip8_run_frame(chip8) == CHIP8_OK);
+    chip8_get_registers(chip8, &reg);
+    CHECK(reg.dt == 9);
+
+    chip8_destroy(chip8);
+}
+
+static void test_save_states(void)
+{
+    Chip8 *chip8 = chip8_create(NULL, 2);
+    Chip8Registers saved, reg;
+    size_t size = chip8_state_size();
+    uint8_t *state = malloc(size);
+
+    CHECK(chip8_load_rom(chip8, KEY_ROM, sizeof KEY_ROM) == CHIP8_OK);
+    CHECK(chip8_run_frame(chip8) == CHIP8_OK);
+    chip8_get_registers(chip8, &saved);
+
+    CHECK(chip8_save_state(chip8, state, size - 1) == CHIP8_ERROR);
+    CHECK(chip8_save_state(chip8, state, size) == CHIP8_OK);
+
+    chip8_set_key(chip8, 5);
+    CHECK(chip8_run_frame(chip8) == CHIP8_OK);
+    reg = saved;
+    reg.v[7] = 42;
+    CHECK(chip8_set_registers(chip8, &reg) == CHIP8_OK);
+
+    CHECK(chip8_load_state(chip8, state, size) == CHIP8_OK);
+    chip8_get_registers(chip8, &reg);
+    CHECK(memcmp(&reg, &saved, sizeof reg) == 0);
+    CHECK(chip8_framebuffer(chip8)[0] == 1);
+
+    CHECK(chip8_load_state(chip8, state, size - 1) == CHIP8_ERROR);
+    CHECK(strlen(chip8_last_error(chip8)) > 0);
+
+    free(state);
+    chip8_destroy(chip8);
+}
+
+static void test_errors(void)
+{
+    Chip8 *chip8 = chip8_create(NULL, 3);
+    static uint8_t too_big[CHIP8_MAX_ROM_SIZE + 1];
+
+    CHECK(chip8_load_rom(chip8, too_big, sizeof too_big) == CHIP8_ERROR);
+    CHECK(strstr(chip8_last_error(chip8), "ROM") != NULL);
+
+    CHECK(chip8_load_rom(chip8, CRASH_ROM, sizeof CRASH_ROM) == CHIP8_OK);
+    int result = CHIP8_OK;
+    for (int frame = 0; frame < 10 && result == CHIP8_OK; frame++)
+        result = chip8_run_frame(chip8);
+    CHECK(result == CHIP8_ERROR);
+    CHECK(strstr(chip8_last_error(chip8), "stack") != NULL);
+    CHECK(chip8_step(chip8) == CHIP8_ERROR);
+
+    /* loading a ROM brings it back */
+    CHECK(chip8_load_rom(chip8, KEY_ROM, sizeof KEY_ROM) == CHIP8_OK);
+    CHECK(chip8_run_frame(chip8) == CHIP8_OK);
+
+    /* registers pointing outside memory are refused */
+    Chip8Registers reg, bad;
+    chip8_get_registers(chip8, &reg);
+    bad = reg;
+    bad.pc = 0x1000;
+    CHECK(chip8_set_registers(chip8, &bad) == CHIP8_ERROR);
+    bad = reg;
+    bad.i = 0xffff;
+    CHECK(chip8_set_registers(chip8, &bad) == CHIP8_ERROR);
+    bad = reg;
+    bad.sp = 0xfff;
+    CHECK(chip8_set_registers(chip8, &bad) == CHIP8_ERROR);
+    CHECK(strstr(chip8_last_error(chip8), "SP") != NULL);
+    chip8_get_registers(chip8, &bad);
+    CHECK(memcmp(&reg, &bad, sizeof reg) == 0);
+
+    /* an unknown opcode is an error, not a crash */
+    static const uint8_t BAD_OPCODE_ROM[] = {0xf0, 0xff};
+    CHECK(chip8_load_rom(chip8, BAD_OPCODE_ROM, sizeof BAD_OPCODE_ROM) == CHIP8_OK);
+    CHECK(chip8_step(chip8) == CHIP8_ERROR);
+    CHECK(strstr(chip8_last_error(chip8), "not an instruction") != NULL);
+
+    chip8_destroy(chip8);
+}
+
+int main(void)
+{
+    test_create();
+    test_step_and_keys();
+    test_save_states();
+    test_errors();
+
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("all checks passed\n");
+    return 0;
+}
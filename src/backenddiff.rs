@@ -0,0 +1,183 @@
+use std::fs;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::config::{Config, Frontend};
+use crate::machine::Machine;
+use crate::quirks::Quirks;
+use crate::recompiler::Backend;
+use crate::rewind::RewindConfig;
+use crate::session::Session;
+use crate::timing::Timing;
+
+//Differential check of the recompiler against the interpreter. Each ROM is
+//run on both with the same seed and the same made up keypad input, and the
+//machines are compared after every frame. The first frame they disagree on
+//is reported with what differs.
+
+const USAGE: &str = "usage: chip8 backend-diff [--frames N] [--seed N] [--quirks vip|chip48|schip|octo] [--ipf N] <rom>...";
+
+struct Options {
+    frames: u64,
+    seed: u64,
+    quirks: Quirks,
+    rom_paths: Vec<String>,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        frames: 3600,
+        seed: 0,
+        quirks: Quirks::default(),
+        rom_paths: Vec::new(),
+    };
+    let mut ipf = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        if let Some(key) = arg.strip_prefix("--") {
+            let value = iter.next().ok_or(USAGE.to_string())?;
+            match key {
+                "frames" => options.frames = value.parse()
+                    .map_err(|_| format!("bad frame count '{}'", value))?,
+                "seed" => options.seed = value.parse()
+                    .map_err(|_| format!("bad seed '{}'", value))?,
+                "quirks" => options.quirks = Quirks::from_profile(value)
+                    .ok_or(format!("unknown profile '{}'", value))?,
+                "ipf" => ipf = Some(value.parse()
+                    .ok().filter(|ipf| *ipf > 0)
+                    .ok_or(format!("bad instructions per frame '{}'", value))?),
+                _ => return Err(format!("unknown option '{}'\n{}", key, USAGE)),
+            }
+        } else {
+            options.rom_paths.push(arg.clone());
+        }
+    }
+
+    if options.rom_paths.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    //on top of the profile, whichever came first
+    if let Some(ipf) = ipf {
+        options.quirks.instructions_per_frame = ipf;
+    }
+    if options.quirks.timing == Timing::Vip {
+        return Err("backend-diff can't compare with vip timing, the recompiler only supports fixed timing".to_string());
+    }
+
+    Ok(options)
+}
+
+//keys are held for a few frames at a time like a player would, with gaps
+fn next_key(rng: &mut ChaCha8Rng, key: u8) -> u8 {
+    match rng.gen_range(0..8) {
+        0 => 0xff,
+        1 => rng.gen_range(0..16),
+        _ => key,
+    }
+}
+
+fn describe(ours: &Machine, theirs: &Machine) -> Vec<String> {
+    let mut differences = Vec::new();
+    let (a, b) = (&ours.reg, &theirs.reg);
+
+    for x in 0..16 {
+        if a.V[x] != b.V[x] {
+            differences.push(format!("v{:x} is {:02x}, interpreter has {:02x}", x, a.V[x], b.V[x]));
+        }
+    }
+    for (name, mine, other) in [("pc", a.PC, b.PC), ("i", a.I, b.I), ("sp", a.SP, b.SP),
+        ("dt", a.DT as u16, b.DT as u16), ("st", a.ST as u16, b.ST as u16)] {
+        if mine != other {
+            differences.push(format!("{} is {:03x}, interpreter has {:03x}", name, mine, other));
+        }
+    }
+    if ours.cycle != theirs.cycle {
+        differences.push(format!("ran {} instructions, interpreter ran {}", ours.cycle, theirs.cycle));
+    }
+
+    let memory: Vec<usize> = (0..4096).filter(|addr| ours.memory[*addr] != theirs.memory[*addr]).collect();
+    if let Some(first) = memory.first() {
+        differences.push(format!("{} bytes of memory differ, first at {:03x}", memory.len(), first));
+    }
+
+    let pixels = ours.display_mem.iter().flatten()
+        .zip(theirs.display_mem.iter().flatten())
+        .filter(|(mine, other)| mine != other)
+        .count();
+    if pixels > 0 {
+        differences.push(format!("{} pixels differ", pixels));
+    }
+
+    differences
+}
+
+//Ok(true) when the backends agree on every frame
+fn compare(options: &Options, rom_path: &str) -> Result<bool, String> {
+    let chp8_code = fs::read(rom_path)
+        .map_err(|e| format!("could not read {}: {}", rom_path, e))?;
+
+    let interpreter_config = Config {
+        rom_path: rom_path.to_string(),
+        frontend: Frontend::Headless,
+        seed: Some(options.seed),
+        history: 0,
+        rewind: RewindConfig { frames: 0, ..RewindConfig::default() },
+        cheat_dir: None,
+        quirks: options.quirks.clone(),
+        ..Config::default()
+    };
+    let recompiler_config = Config {
+        backend: Backend::Recompiler,
+        ..interpreter_config.clone()
+    };
+
+    let mut interpreter = Session::new(&chp8_code, &interpreter_config)?;
+    let mut recompiler = Session::new(&chp8_code, &recompiler_config)?;
+    let mut rng = ChaCha8Rng::seed_from_u64(options.seed);
+    let mut key = 0xff;
+
+    for frame in 0..options.frames {
+        key = next_key(&mut rng, key);
+
+        let expected = interpreter.run_frame(key);
+        let result = recompiler.run_frame(key);
+
+        match (&expected, &result) {
+            (Err(_), Err(_)) => {
+                println!("{}: both backends crashed on frame {}", rom_path, frame);
+                return Ok(true);
+            },
+            (Err(e), Ok(_)) | (Ok(_), Err(e)) => {
+                let backend = if expected.is_err() { "interpreter" } else { "recompiler" };
+                println!("{}: only the {} failed on frame {}: {}", rom_path, backend, frame, e);
+                return Ok(false);
+            },
+            (Ok(_), Ok(_)) => {},
+        }
+
+        if recompiler.machine.state_hash() != interpreter.machine.state_hash() {
+            println!("{}: backends diverge on frame {}:", rom_path, frame);
+            for difference in describe(&recompiler.machine, &interpreter.machine) {
+                println!("  {}", difference);
+            }
+            return Ok(false);
+        }
+    }
+
+    println!("{}: {} frames, {} instructions match", rom_path, options.frames, interpreter.machine.cycle);
+    Ok(true)
+}
+
+//args are everything after "backend-diff", Ok(true) when every ROM matches
+pub fn run(args: &[String]) -> Result<bool, String> {
+    let options = parse_args(args)?;
+    let mut matched = true;
+
+    for rom_path in &options.rom_paths {
+        matched &= compare(&options, rom_path)?;
+    }
+
+    Ok(matched)
+}
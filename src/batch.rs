@@ -0,0 +1,275 @@
+use std::fs;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::environment::{EnvSpec, Environment, StepResult, OBSERVATION_SIZE};
+
+//Many environments on one ROM stepped in lockstep, for evaluating a batch of
+//agents or episodes at once. Each instance has its own machine and shares
+//only the ROM image, so they can run on separate threads without any locking.
+//The instances are split between worker threads that live as long as the
+//batch and are handed each step over a channel, so a step costs two channel
+//messages per thread rather than starting threads. A step takes one set of
+//keys per instance and hands back the displays stacked one after the other,
+//with a reward and done flag per instance. Instances whose episode ended
+//start a new one on their next step, so the batch never stalls waiting on its
+//slowest member. An instance whose machine crashes ends its episode there,
+//with the error in BatchStep::errors, and the rest of the batch carries on.
+
+const USAGE: &str = "usage: chip8 batch [--instances N] [--steps N] [--threads N] [--seed N] <spec>
+
+Steps a batch of random agents through the environment the spec describes
+and reports the throughput, to size batches for a machine.";
+
+pub struct BatchStep {
+    //OBSERVATION_SIZE bytes per instance, in instance order
+    pub observations: Vec<u8>,
+    pub rewards: Vec<f32>,
+    pub done: Vec<bool>,
+    //why an instance's episode ended early, None unless its machine crashed
+    pub errors: Vec<Option<String>>,
+}
+
+enum Job {
+    Reset,
+    //keys for each of the worker's instances
+    Step(Vec<u16>),
+}
+
+//what a job did to each of a worker's instances
+type Results = Vec<Result<StepResult, String>>;
+
+struct Worker {
+    jobs: Sender<Job>,
+    results: Receiver<Results>,
+    handle: JoinHandle<()>,
+    //instances this worker runs
+    count: usize,
+}
+
+//runs jobs on its share of the instances until the batch is dropped
+fn work(mut instances: Vec<Environment>, jobs: Receiver<Job>, results: Sender<Results>) {
+    //whether an instance needs a reset before it can step again
+    let mut needs_reset = vec![true; instances.len()];
+
+    for job in jobs {
+        let result = match job {
+            Job::Reset => instances.iter_mut().zip(&mut needs_reset).map(|(env, needs_reset)| {
+                let observation = env.reset()?;
+                *needs_reset = false;
+                Ok(StepResult { observation, reward: 0.0, done: false })
+            }).collect(),
+            Job::Step(keys) => instances.iter_mut().zip(&mut needs_reset).zip(keys).map(|((env, needs_reset), keys)| {
+                if *needs_reset {
+                    env.reset()?;
+                }
+                //a crash only ends this instance's episode
+                let result = env.step(keys);
+                *needs_reset = result.as_ref().map_or(true, |result| result.done);
+                result
+            }).collect(),
+        };
+
+        if results.send(result).is_err() {
+            break;
+        }
+    }
+}
+
+pub struct Batch {
+    workers: Vec<Worker>,
+    len: usize,
+}
+
+impl Batch {
+    //instance i is seeded with seed + (i << 32), so episodes of different
+    //instances never share a seed
+    pub fn new(spec: &EnvSpec, count: usize, seed: u64, threads: usize) -> Result<Batch, String> {
+        if count == 0 {
+            return Err("a batch needs at least one instance".to_string());
+        }
+
+        let chp8_code: Arc<[u8]> = fs::read(&spec.rom_path)
+            .map_err(|e| format!("could not read {}: {}", spec.rom_path, e))?
+            .into();
+        let mut instances = (0..count)
+            .map(|i| Environment::with_code(spec.clone(), chp8_code.clone(), seed.wrapping_add((i as u64) << 32)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let chunk = count.div_ceil(threads.clamp(1, count));
+        let mut workers = Vec::new();
+        while !instances.is_empty() {
+            let rest = instances.split_off(chunk.min(instances.len()));
+            let share = std::mem::replace(&mut instances, rest);
+            let (jobs, job_receiver) = mpsc::channel();
+            let (result_sender, results) = mpsc::channel();
+            let count = share.len();
+
+            workers.push(Worker {
+                jobs,
+                results,
+                handle: thread::spawn(move || work(share, job_receiver, result_sender)),
+                count,
+            });
+        }
+
+        Ok(Batch { workers, len: count })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    //hands every worker its job before waiting on any, so they run together,
+    //and waits on all of them so no results are left over for the next job
+    fn run(&mut self, jobs: Vec<Job>) -> Result<Results, String> {
+        let sent: Vec<bool> = self.workers.iter().zip(jobs)
+            .map(|(worker, job)| worker.jobs.send(job).is_ok())
+            .collect();
+
+        let mut results = Vec::with_capacity(self.len);
+        let mut failed = false;
+        for (worker, sent) in self.workers.iter().zip(sent) {
+            match worker.results.recv() {
+                Ok(worker_results) if sent => results.extend(worker_results),
+                _ => failed = true,
+            }
+        }
+
+        if failed {
+            return Err("a batch worker panicked".to_string());
+        }
+        Ok(results)
+    }
+
+    //starts every instance on a new episode, returns the stacked displays
+    pub fn reset(&mut self) -> Result<Vec<u8>, String> {
+        let jobs = self.workers.iter().map(|_| Job::Reset).collect();
+        let results = self.run(jobs)?.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        Ok(results.into_iter().flat_map(|result| result.observation).collect())
+    }
+
+    //keys holds the keys for each instance, bit n for key n
+    pub fn step(&mut self, keys: &[u16]) -> Result<BatchStep, String> {
+        if keys.len() != self.len() {
+            return Err(format!("got {} keys for {} instances", keys.len(), self.len()));
+        }
+
+        let mut keys = keys.iter().copied();
+        let jobs = self.workers.iter()
+            .map(|worker| Job::Step(keys.by_ref().take(worker.count).collect()))
+            .collect();
+        let results = self.run(jobs)?;
+
+        let mut step = BatchStep {
+            observations: Vec::with_capacity(self.len() * OBSERVATION_SIZE),
+            rewards: Vec::with_capacity(self.len()),
+            done: Vec::with_capacity(self.len()),
+            errors: Vec::with_capacity(self.len()),
+        };
+        for result in results {
+            match result {
+                Ok(result) => {
+                    step.observations.extend(result.observation);
+                    step.rewards.push(result.reward);
+                    step.done.push(result.done);
+                    step.errors.push(None);
+                },
+                Err(e) => {
+                    //the display is left blank, the instance starts over next step
+                    step.observations.extend([0; OBSERVATION_SIZE]);
+                    step.rewards.push(0.0);
+                    step.done.push(true);
+                    step.errors.push(Some(e));
+                },
+            }
+        }
+
+        Ok(step)
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        //closing a worker's job channel ends its loop
+        for worker in self.workers.drain(..) {
+            let Worker { jobs, handle, .. } = worker;
+            drop(jobs);
+            let _ = handle.join();
+        }
+    }
+}
+
+//args are everything after "batch"
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut instances = 16;
+    let mut steps = 1000;
+    let mut threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut seed = 0;
+    let mut spec_path = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let mut number = |name: &str| -> Result<u64, String> {
+            let value = iter.next().ok_or(USAGE.to_string())?;
+            value.parse().map_err(|_| format!("bad {} '{}'", name, value))
+        };
+
+        match arg.as_str() {
+            "--instances" => instances = number("instance count")? as usize,
+            "--steps" => steps = number("step count")?,
+            "--threads" => threads = number("thread count")? as usize,
+            "--seed" => seed = number("seed")?,
+            _ if spec_path.is_none() && !arg.starts_with("--") => spec_path = Some(arg.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let spec = EnvSpec::load(&spec_path.ok_or(USAGE.to_string())?)?;
+    let frame_skip = spec.frame_skip as u64;
+    let mut batch = Batch::new(&spec, instances, seed, threads)?;
+    let mut agent = ChaCha8Rng::seed_from_u64(seed);
+    let mut episodes = 0;
+    let mut crashes = 0;
+    let mut total = 0.0;
+
+    batch.reset()?;
+    let started = Instant::now();
+    for _ in 0..steps {
+        //one of the sixteen keys or none for every instance
+        let keys: Vec<u16> = (0..batch.len())
+            .map(|_| match agent.gen_range(0..17) {
+                16 => 0,
+                key => 1 << key,
+            })
+            .collect();
+        let step = batch.step(&keys)?;
+        total += step.rewards.iter().sum::<f32>();
+        episodes += step.done.iter().filter(|done| **done).count();
+        crashes += step.errors.iter().filter(|error| error.is_some()).count();
+    }
+    let seconds = started.elapsed().as_secs_f64().max(1e-9);
+
+    //frame skip is an upper bound, steps that end an episode can run fewer frames
+    let instance_steps = steps * batch.len() as u64;
+    println!("{} instances on {} threads, {} steps each", batch.len(), batch.threads(), steps);
+    println!("{:.0} steps/s, up to {:.0} frames/s", instance_steps as f64 / seconds,
+        (instance_steps * frame_skip) as f64 / seconds);
+    println!("{} episodes finished, {} by a crash, total reward {}", episodes, crashes, total);
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::fs;
+use std::time::Instant;
+
+use crate::decode::DecodeCache;
+use crate::machine::Machine;
+use crate::quirks::Quirks;
+use crate::recompiler::{Backend, Recompiler};
+
+//Throughput on real ROMs of the interpreter with the decode cache off and on,
+//and of the recompiler. Only the machine is run, no session, so history,
+//tracing and the like don't get in the way of the numbers. Every run has to
+//end in the same state, which also catches a cache missing a write to code.
+
+const USAGE: &str = "usage: chip8 bench [--instructions N] [--quirks vip|chip48|schip|octo] <rom>...";
+
+struct Run {
+    seconds: f64,
+    state_hash: u64,
+}
+
+fn run_machine(chp8_code: &[u8], quirks: &Quirks, instructions: u64, backend: Backend, cached: bool)
+    -> Result<Run, String> {
+
+    let mut machine = Machine::new(chp8_code, 0, quirks)?;
+    machine.decode_cache = DecodeCache::new(cached);
+    let mut recompiler = Recompiler::new();
+    let ipf = quirks.instructions_per_frame as u64;
+
+    let started = Instant::now();
+    while machine.cycle < instructions {
+        //frames are cut short at the instruction count so every run stops in the same place
+        let remaining = (ipf - (machine.cycle - machine.frame_start_cycle)).min(instructions - machine.cycle);
+        let result = match backend {
+            Backend::Interpreter => machine.step(),
+            Backend::Recompiler => recompiler.run(&mut machine, remaining).map(|_| ()),
+        };
+        result.map_err(|e| format!("machine crashed after {} instructions: {}", machine.cycle, e))?;
+        if machine.cycle - machine.frame_start_cycle >= ipf {
+            machine.tick_timers();
+        }
+    }
+
+    Ok(Run {
+        seconds: started.elapsed().as_secs_f64().max(1e-9),
+        state_hash: machine.state_hash(),
+    })
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut instructions: u64 = 10_000_000;
+    let mut quirks = Quirks::default();
+    let mut rom_paths = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--instructions" => {
+                let value = iter.next().ok_or(USAGE.to_string())?;
+                instructions = value.parse()
+                    .map_err(|_| format!("bad instruction count '{}'", value))?;
+            },
+            "--quirks" => {
+                let value = iter.next().ok_or(USAGE.to_string())?;
+                quirks = Quirks::from_profile(value)
+                    .ok_or(format!("unknown profile '{}'", value))?;
+            },
+            _ if arg.starts_with("--") => return Err(USAGE.to_string()),
+            _ => rom_paths.push(arg.clone()),
+        }
+    }
+
+    if rom_paths.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    println!("{:<32} {:>12} {:>12} {:>12} {:>8}", "rom", "uncached", "cached", "recompiled", "speedup");
+
+    for rom_path in &rom_paths {
+        let chp8_code = fs::read(rom_path)
+            .map_err(|e| format!("could not read {}: {}", rom_path, e))?;
+
+        let run = |backend, cached| run_machine(&chp8_code, &quirks, instructions, backend, cached)
+            .map_err(|e| format!("{}: {}", rom_path, e));
+        let uncached = run(Backend::Interpreter, false)?;
+        let cached = run(Backend::Interpreter, true)?;
+        let recompiled = run(Backend::Recompiler, true)?;
+
+        for (name, result) in [("decode cache", &cached), ("recompiler", &recompiled)] {
+            if result.state_hash != uncached.state_hash {
+                return Err(format!("{}: the {} changed the outcome, state hash {:016x} instead of {:016x}",
+                    rom_path, name, result.state_hash, uncached.state_hash));
+            }
+        }
+
+        //speedup is the fastest run against the plain interpreter
+        let mips = |run: &Run| instructions as f64 / run.seconds / 1e6;
+        let fastest = cached.seconds.min(recompiled.seconds);
+        println!("{:<32} {:>8.1} M/s {:>8.1} M/s {:>8.1} M/s {:>7.2}x", rom_path,
+            mips(&uncached), mips(&cached), mips(&recompiled), uncached.seconds / fastest);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,257 @@
+//what callers must pass is spelled out once, in the header
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::slice;
+
+use crate::frame::FrameRunner;
+use crate::machine::{Machine, MAX_ROM_SIZE, STACK_BASE, STATE_SIZE};
+use crate::quirks::Quirks;
+use crate::recompiler::Backend;
+
+//C interface to the core, declared in capi/chip8.h. A Chip8 is an opaque
+//handle owning one machine. Calls returning int give CHIP8_OK or CHIP8_ERROR,
+//and chip8_last_error says what went wrong. A machine that crashes stays
+//crashed until a ROM or state is loaded again. The layout of Chip8Registers
+//and the meaning of existing functions don't change, new functions bump
+//CHIP8_API_VERSION.
+
+pub const CHIP8_API_VERSION: u32 = 1;
+
+pub const CHIP8_OK: c_int = 0;
+pub const CHIP8_ERROR: c_int = -1;
+
+pub const CHIP8_DISPLAY_WIDTH: usize = 64;
+pub const CHIP8_DISPLAY_HEIGHT: usize = 32;
+
+//largest ROM that fits between 0x200 and the end of memory
+pub const CHIP8_MAX_ROM_SIZE: usize = MAX_ROM_SIZE;
+
+#[repr(C)]
+pub struct Chip8Registers {
+    pub v: [u8; 16],
+    pub dt: u8,
+    pub st: u8,
+    pub i: u16,
+    pub sp: u16,
+    pub pc: u16,
+}
+
+pub struct Chip8 {
+    machine: Machine,
+    quirks: Quirks,
+    seed: u64,
+    runner: FrameRunner,
+    //the display one byte of 0 or 1 per pixel, row by row, kept up to date
+    //after every call that can change it
+    framebuffer: [u8; CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT],
+    crashed: bool,
+    error: CString,
+}
+
+impl Chip8 {
+    fn fail(&mut self, error: String) -> c_int {
+        //an error never has a nul in it, but don't lose it if it somehow does
+        self.error = CString::new(error.replace('\0', " ")).unwrap();
+        CHIP8_ERROR
+    }
+
+    fn finish(&mut self, result: Result<(), String>) -> c_int {
+        for y in 0..CHIP8_DISPLAY_HEIGHT {
+            for x in 0..CHIP8_DISPLAY_WIDTH {
+                self.framebuffer[y * CHIP8_DISPLAY_WIDTH + x] = self.machine.display_mem[x][y];
+            }
+        }
+
+        match result {
+            Ok(()) => CHIP8_OK,
+            Err(error) => {
+                self.crashed = true;
+                self.fail(error)
+            },
+        }
+    }
+
+    fn reset(&mut self, chp8_code: &[u8]) -> Result<(), String> {
+        self.machine = Machine::new(chp8_code, self.seed, &self.quirks)?;
+        //the interpreter backend can't fail to set up
+        self.runner = FrameRunner::new(&self.quirks, Backend::Interpreter).unwrap();
+        self.crashed = false;
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_api_version() -> u32 {
+    CHIP8_API_VERSION
+}
+
+//quirks names a profile, vip, chip48, schip or octo, or is NULL for the
+//default. seed drives Cxkk. NULL if the profile is unknown
+#[no_mangle]
+pub unsafe extern "C" fn chip8_create(quirks: *const c_char, seed: u64) -> *mut Chip8 {
+    let quirks = if quirks.is_null() {
+        Quirks::default()
+    } else {
+        match CStr::from_ptr(quirks).to_str().ok().and_then(Quirks::from_profile) {
+            Some(quirks) => quirks,
+            None => return ptr::null_mut(),
+        }
+    };
+
+    let mut chip8 = Box::new(Chip8 {
+        //the profiles all keep the font below 200
+        machine: Machine::new(&[], seed, &quirks).unwrap(),
+        runner: FrameRunner::new(&quirks, Backend::Interpreter).unwrap(),
+        quirks,
+        seed,
+        framebuffer: [0; CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT],
+        crashed: false,
+        error: CString::default(),
+    });
+    chip8.finish(Ok(()));
+
+    Box::into_raw(chip8)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_destroy(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+//the last error on this machine, valid until the next call that fails
+#[no_mangle]
+pub unsafe extern "C" fn chip8_last_error(chip8: *const Chip8) -> *const c_char {
+    (*chip8).error.as_ptr()
+}
+
+//starts the machine over with a new ROM, the display cleared and nothing held
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, rom: *const u8, len: usize) -> c_int {
+    let chip8 = &mut *chip8;
+    if len > CHIP8_MAX_ROM_SIZE {
+        return chip8.fail(format!("ROM is {} bytes, at most {} fit", len, CHIP8_MAX_ROM_SIZE));
+    }
+    if rom.is_null() && len > 0 {
+        return chip8.fail("ROM is NULL".to_string());
+    }
+
+    let chp8_code = if len == 0 { &[] } else { slice::from_raw_parts(rom, len) };
+    let result = chip8.reset(chp8_code);
+    chip8.finish(result)
+}
+
+//key 0 to f held down from now on, 0xff for none
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8) {
+    (*chip8).machine.set_key(key);
+}
+
+//runs one instruction, ticking the timers when it was the frame's last
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8) -> c_int {
+    let chip8 = &mut *chip8;
+    if chip8.crashed {
+        return chip8.fail("the machine has crashed, load a ROM or state first".to_string());
+    }
+
+    let result = chip8.runner.step(&mut chip8.machine);
+    chip8.finish(result)
+}
+
+//runs to the end of the current frame and ticks the timers
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip8: *mut Chip8) -> c_int {
+    let chip8 = &mut *chip8;
+    if chip8.crashed {
+        return chip8.fail("the machine has crashed, load a ROM or state first".to_string());
+    }
+
+    let keys = chip8.machine.keys;
+    let result = chip8.runner.run_frame(&mut chip8.machine, keys);
+    chip8.finish(result)
+}
+
+//CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT bytes of 0 or 1, row by row,
+//owned by the machine and valid until it is destroyed
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const Chip8) -> *const u8 {
+    (*chip8).framebuffer.as_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_registers(chip8: *const Chip8, registers: *mut Chip8Registers) {
+    let reg = &(*chip8).machine.reg;
+    *registers = Chip8Registers {
+        v: reg.V,
+        dt: reg.DT,
+        st: reg.ST,
+        i: reg.I,
+        sp: reg.SP,
+        pc: reg.PC,
+    };
+}
+
+//PC and I must be inside memory and SP inside the stack, or nothing is set
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_registers(chip8: *mut Chip8, registers: *const Chip8Registers) -> c_int {
+    let chip8 = &mut *chip8;
+    let registers = &*registers;
+    if registers.pc > 0xfff {
+        return chip8.fail(format!("PC {:x} is outside memory", registers.pc));
+    }
+    if registers.i > 0xfff {
+        return chip8.fail(format!("I {:x} is outside memory", registers.i));
+    }
+    if registers.sp < STACK_BASE || registers.sp > 0xffe {
+        return chip8.fail(format!("SP {:x} is outside the stack at {:x}-fff", registers.sp, STACK_BASE));
+    }
+
+    let reg = &mut chip8.machine.reg;
+    reg.V = registers.v;
+    reg.DT = registers.dt;
+    reg.ST = registers.st;
+    reg.I = registers.i;
+    reg.SP = registers.sp;
+    reg.PC = registers.pc;
+    CHIP8_OK
+}
+
+//bytes in a save state, the same for every machine
+#[no_mangle]
+pub extern "C" fn chip8_state_size() -> usize {
+    STATE_SIZE
+}
+
+//writes chip8_state_size() bytes to buffer, which holds len
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(chip8: *mut Chip8, buffer: *mut u8, len: usize) -> c_int {
+    let chip8 = &mut *chip8;
+    if buffer.is_null() || len < STATE_SIZE {
+        return chip8.fail(format!("a save state needs a buffer of {} bytes", STATE_SIZE));
+    }
+
+    let state = chip8.machine.save_state();
+    slice::from_raw_parts_mut(buffer, state.len()).copy_from_slice(&state);
+    CHIP8_OK
+}
+
+//states are the same format the chip8 binary saves, so they can be passed
+//back and forth
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(chip8: *mut Chip8, state: *const u8, len: usize) -> c_int {
+    let chip8 = &mut *chip8;
+    if state.is_null() {
+        return chip8.fail("state is NULL".to_string());
+    }
+
+    if let Err(error) = chip8.machine.load_state(slice::from_raw_parts(state, len)) {
+        return chip8.fail(error);
+    }
+    chip8.crashed = false;
+    chip8.finish(Ok(()))
+}
@@ -0,0 +1,86 @@
+use crate::config::Config;
+use crate::machine::Machine;
+use crate::recorder::Recorder;
+use crate::screenshot;
+
+//screenshots and recordings, shared by every frontend and driven once per frame
+pub struct Capture<'a> {
+    config: &'a Config,
+    recorder: Option<Recorder>,
+    want_screenshot: bool,
+    want_toggle: bool,
+}
+
+impl<'a> Capture<'a> {
+    pub fn new(config: &'a Config) -> Result<Capture<'a>, String> {
+        let recorder = match &config.record {
+            Some(path) => Some(Recorder::start(path, &config.display.palette, config.display.scale)?),
+            None => None,
+        };
+
+        Ok(Capture {
+            config,
+            recorder,
+            want_screenshot: false,
+            want_toggle: false,
+        })
+    }
+
+    //hotkeys, both take effect at the end of the current frame
+    pub fn request_screenshot(&mut self) {
+        self.want_screenshot = true;
+    }
+
+    pub fn toggle_recording(&mut self) {
+        self.want_toggle = true;
+    }
+
+    //returns a message for the frontend to show when something was written
+    pub fn end_frame(&mut self, machine: &Machine) -> Result<Option<String>, String> {
+        let display = &self.config.display;
+        let mut status = None;
+
+        if self.want_toggle {
+            self.want_toggle = false;
+
+            status = Some(match self.recorder.take() {
+                Some(recorder) => {
+                    let path = recorder.path.clone();
+                    recorder.finish()?;
+                    format!("saved recording {}", path.display())
+                },
+                None => {
+                    let path = screenshot::timestamped_path(&self.config.screenshot.dir, "gif")?;
+                    self.recorder = Some(Recorder::start(&path, &display.palette, display.scale)?);
+                    format!("recording to {}", path.display())
+                },
+            });
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.capture(&machine.display_mem)?;
+        }
+
+        if self.config.screenshot.at_frame == Some(machine.frame) {
+            self.want_screenshot = true;
+        }
+
+        if self.want_screenshot {
+            self.want_screenshot = false;
+
+            let written = screenshot::take_screenshot(&machine.display_mem, &self.config.screenshot,
+                &display.palette, display.scale)?;
+            status = Some(format!("saved {}", written[0].display()));
+        }
+
+        Ok(status)
+    }
+
+    //closes any recording still in progress
+    pub fn finish(self) -> Result<(), String> {
+        match self.recorder {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::disasm;
+use crate::machine::Machine;
+use crate::quirks::Quirks;
+
+//Static control flow graph of a ROM. Everything reachable from 0x200 is
+//followed through jumps, calls, returns and skips, then split into basic
+//blocks. Bnnn targets come from the range V0 can hold at the jump, worked out
+//from the instructions earlier in the same block. Whatever ROM bytes are never
+//reached are reported as unreachable, which includes sprite data.
+
+const USAGE: &str = "usage: chip8 cfg [-o FILE.dot] <rom>";
+
+//a Bnnn is only followed when V0 is known to hold at most this many values
+const MAX_TABLE_TARGETS: u16 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum EdgeKind {
+    Next,
+    Jump,
+    Call,
+    SkipTaken,
+    SkipNotTaken,
+    //target of a Bnnn for one value of V0
+    Table(u8),
+}
+
+#[derive(Clone, Debug, Default)]
+struct Analysis {
+    //reachable instruction addresses and where each one can go
+    instructions: BTreeMap<u16, Vec<(u16, EdgeKind)>>,
+    //Bnnn instructions and the V0 range they were followed with, None when unbounded
+    tables: BTreeMap<u16, Option<(u8, u8)>>,
+    //instruction writing memory and the reachable code it overwrites
+    self_modifying: BTreeMap<u16, (u16, u16)>,
+    //instructions that aren't valid opcodes
+    invalid: BTreeSet<u16>,
+}
+
+//instructions that end a basic block
+fn is_branch(instruction: u16) -> bool {
+    let opcode = instruction >> 12;
+    disasm::is_skip(instruction) || instruction == 0x00ee || matches!(opcode, 0x1 | 0x2 | 0xb)
+}
+
+//what an instruction does to the range of values V0 can hold
+fn update_v0(range: Option<(u8, u8)>, instruction: u16) -> Option<(u8, u8)> {
+    let x = (instruction >> 8) & 0xf;
+    let kk = (instruction & 0xff) as u8;
+
+    match (instruction >> 12, x, instruction & 0xff) {
+        (0x6, 0, _) => Some((kk, kk)),
+        (0x7, 0, _) => range.and_then(|(lo, hi)| {
+            //a wrap around splits the range, give up on it
+            Some((lo.checked_add(kk)?, hi.checked_add(kk)?))
+        }),
+        (0xc, 0, _) => Some((0, kk)),
+        //AND with a constant can't be seen statically, anything else writing V0 loses it
+        (0x8, 0, _) | (0xf, 0, 0x07) | (0xf, 0, 0x0a) | (0xf, _, 0x65) => None,
+        _ => range,
+    }
+}
+
+//a Bnnn reached again with a different V0 covers both ranges, and stays
+//unbounded once it was reached with V0 unknown
+fn merge_table(analysis: &mut Analysis, addr: u16, range: Option<(u8, u8)>) {
+    analysis.tables.entry(addr)
+        .and_modify(|known| *known = match (*known, range) {
+            (Some((lo, hi)), Some((new_lo, new_hi))) => Some((lo.min(new_lo), hi.max(new_hi))),
+            _ => None,
+        })
+        .or_insert(range);
+}
+
+fn successors(addr: u16, instruction: u16, v0: Option<(u8, u8)>, analysis: &mut Analysis)
+    -> Vec<(u16, EdgeKind)> {
+
+    let nnn = instruction & 0x0fff;
+    let next = addr.wrapping_add(2) & 0xfff;
+
+    match instruction >> 12 {
+        _ if instruction == 0x00ee => vec![],
+        0x1 => vec![(nnn, EdgeKind::Jump)],
+        0x2 => vec![(nnn, EdgeKind::Call), (next, EdgeKind::Next)],
+        0xb => match v0 {
+            Some((lo, hi)) if ((hi - lo) as u16) < MAX_TABLE_TARGETS => {
+                merge_table(analysis, addr, Some((lo, hi)));
+                //every V0 is followed, the graph marks the ones landing on odd addresses
+                (lo..=hi)
+                    .map(|v| ((nnn + v as u16) & 0xfff, EdgeKind::Table(v)))
+                    .collect()
+            },
+            _ => {
+                merge_table(analysis, addr, None);
+                vec![]
+            },
+        },
+        _ if disasm::is_skip(instruction) => vec![
+            (next, EdgeKind::SkipNotTaken),
+            (addr.wrapping_add(4) & 0xfff, EdgeKind::SkipTaken),
+        ],
+        _ => vec![(next, EdgeKind::Next)],
+    }
+}
+
+fn is_valid(instruction: u16) -> bool {
+    !disasm::mnemonic(instruction).starts_with("DW")
+}
+
+fn analyze(machine: &Machine) -> Analysis {
+    let mut analysis = Analysis::default();
+    //block entry points still to walk, with what's known about V0 there
+    let mut work = vec![(0x200u16, None::<(u8, u8)>)];
+    let mut walked = BTreeSet::new();
+
+    while let Some((start, entry_v0)) = work.pop() {
+        if !walked.insert((start, entry_v0)) {
+            continue;
+        }
+
+        let mut addr = start;
+        let mut v0 = entry_v0;
+        let mut i = None;
+
+        loop {
+            let instruction = machine.fetch(addr);
+
+            if !is_valid(instruction) {
+                analysis.invalid.insert(addr);
+                analysis.instructions.entry(addr).or_default();
+                break;
+            }
+
+            //I written through before being changed by anything but Annn
+            match (instruction >> 12, instruction & 0xff) {
+                (0xa, _) => i = Some(instruction & 0x0fff),
+                (0xf, 0x55) | (0xf, 0x33) => {
+                    if let Some(target) = i {
+                        let len = if instruction & 0xff == 0x33 { 3 } else { ((instruction >> 8) & 0xf) + 1 };
+                        analysis.self_modifying.insert(addr, (target, len));
+                    }
+                },
+                (0xf, 0x1e) | (0xf, 0x65) => i = None,
+                _ => {},
+            }
+
+            let edges = successors(addr, instruction, v0, &mut analysis);
+            v0 = update_v0(v0, instruction);
+
+            //an instruction reached again, say a Bnnn with another V0, keeps the
+            //edges it had and gains the new ones, the walk only goes on for those
+            let already_seen = analysis.instructions.contains_key(&addr) && addr != start;
+            let known = analysis.instructions.entry(addr).or_default();
+            let added: Vec<_> = edges.iter().filter(|edge| !known.contains(edge)).copied().collect();
+            if already_seen && added.is_empty() {
+                break;
+            }
+            known.extend(added);
+
+            if is_branch(instruction) {
+                for (target, kind) in edges {
+                    //V0 is only carried into the instruction right after a skip or call
+                    let carried = match kind {
+                        EdgeKind::SkipTaken | EdgeKind::SkipNotTaken => v0,
+                        _ => None,
+                    };
+                    work.push((target, carried));
+                }
+                break;
+            }
+
+            match edges.first() {
+                Some((next, _)) => addr = *next,
+                None => break,
+            }
+        }
+    }
+
+    //only writes that land on reachable instructions are interesting
+    let instructions = &analysis.instructions;
+    analysis.self_modifying.retain(|_, (target, len)| {
+        (*target..target.saturating_add(*len)).any(|addr| {
+            instructions.contains_key(&addr) || instructions.contains_key(&addr.wrapping_sub(1))
+        })
+    });
+
+    analysis
+}
+
+struct Block {
+    start: u16,
+    instructions: Vec<u16>,
+    edges: Vec<(u16, EdgeKind)>,
+}
+
+fn basic_blocks(analysis: &Analysis) -> Vec<Block> {
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0x200);
+
+    for (addr, edges) in &analysis.instructions {
+        for (target, kind) in edges {
+            if *kind != EdgeKind::Next || *target != addr.wrapping_add(2) {
+                leaders.insert(*target);
+            }
+        }
+    }
+    for (addr, edges) in &analysis.instructions {
+        //a call returns to the next instruction, which starts a block too
+        if edges.iter().any(|(_, kind)| *kind == EdgeKind::Call) {
+            leaders.insert(addr.wrapping_add(2) & 0xfff);
+        }
+    }
+
+    let mut blocks = Vec::new();
+    for leader in leaders.iter().filter(|addr| analysis.instructions.contains_key(addr)) {
+        let mut block = Block { start: *leader, instructions: Vec::new(), edges: Vec::new() };
+        let mut addr = *leader;
+
+        loop {
+            block.instructions.push(addr);
+            let edges = &analysis.instructions[&addr];
+            let next = edges.iter()
+                .find(|(target, kind)| *kind == EdgeKind::Next && *target == addr.wrapping_add(2))
+                .map(|(target, _)| *target);
+
+            let continues = edges.len() == 1
+                && next.is_some_and(|next| !leaders.contains(&next) && analysis.instructions.contains_key(&next));
+
+            if continues {
+                addr = next.unwrap();
+            } else {
+                block.edges = edges.clone();
+                break;
+            }
+        }
+
+        blocks.push(block);
+    }
+
+    blocks
+}
+
+//runs of ROM bytes no reachable instruction covers
+fn unreachable_ranges(analysis: &Analysis, rom_len: usize) -> Vec<(u16, u16)> {
+    let mut covered = vec![false; 4096];
+    for addr in analysis.instructions.keys() {
+        covered[*addr as usize] = true;
+        covered[(*addr as usize + 1) % 4096] = true;
+    }
+
+    let end = (0x200 + rom_len).min(4096);
+    let mut ranges = Vec::new();
+    let mut addr = 0x200;
+
+    while addr < end {
+        if covered[addr] {
+            addr += 1;
+            continue;
+        }
+        let start = addr;
+        while addr < end && !covered[addr] {
+            addr += 1;
+        }
+        ranges.push((start as u16, (addr - 1) as u16));
+    }
+
+    ranges
+}
+
+fn node_name(addr: u16) -> String {
+    format!("b{:03x}", addr)
+}
+
+fn to_dot(machine: &Machine, analysis: &Analysis, blocks: &[Block], unreachable: &[(u16, u16)]) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "digraph cfg {{").unwrap();
+    writeln!(out, "    node [shape=box fontname=monospace];").unwrap();
+
+    let block_of = |addr: u16| blocks.iter().find(|block| block.instructions.contains(&addr)).map(|b| b.start);
+
+    for block in blocks {
+        let mut label = String::new();
+        let mut attributes = Vec::new();
+
+        for addr in &block.instructions {
+            let instruction = machine.fetch(*addr);
+            write!(label, "{:03x}: {}", addr, disasm::mnemonic(instruction)).unwrap();
+
+            if let Some((target, len)) = analysis.self_modifying.get(addr) {
+                write!(label, "  ; writes code at {:03x}-{:03x}", target, target + len - 1).unwrap();
+                attributes.push("style=filled fillcolor=salmon");
+            }
+            match analysis.tables.get(addr) {
+                Some(Some((lo, hi))) if lo == hi => write!(label, "  ; V0 is always {}", lo).unwrap(),
+                Some(Some((lo, hi))) => write!(label, "  ; jump table, V0 {}-{}", lo, hi).unwrap(),
+                Some(None) => {
+                    write!(label, "  ; jump table, V0 unknown").unwrap();
+                    attributes.push("color=red");
+                },
+                None => {},
+            }
+            if analysis.invalid.contains(addr) {
+                write!(label, "  ; invalid opcode").unwrap();
+                attributes.push("color=red");
+            }
+            label.push_str("\\l");
+        }
+
+        if block.start == 0x200 {
+            attributes.push("penwidth=2");
+        }
+
+        writeln!(out, "    {} [label=\"{}\" {}];", node_name(block.start), label, attributes.join(" ")).unwrap();
+    }
+
+    for block in blocks {
+        for (target_addr, kind) in &block.edges {
+            let target = match block_of(*target_addr) {
+                Some(target) => target,
+                None => continue,
+            };
+            let style = match kind {
+                EdgeKind::Next => String::new(),
+                EdgeKind::Jump => "[label=\"jp\"]".to_string(),
+                EdgeKind::Call => "[label=\"call\" style=dashed]".to_string(),
+                EdgeKind::SkipTaken => "[label=\"skip\" color=darkgreen]".to_string(),
+                EdgeKind::SkipNotTaken => "[label=\"no skip\" color=darkred]".to_string(),
+                //instructions start on even addresses, an odd target lands inside one
+                EdgeKind::Table(v) if target_addr % 2 == 1 => format!("[label=\"V0={} misaligned\" color=orange]", v),
+                EdgeKind::Table(v) => format!("[label=\"V0={}\" color=blue]", v),
+            };
+            writeln!(out, "    {} -> {} {};", node_name(block.start), node_name(target), style).unwrap();
+        }
+    }
+
+    for (start, end) in unreachable {
+        writeln!(out, "    u{:03x} [label=\"{:03x}-{:03x} unreachable ({} bytes)\" style=dashed color=gray fontcolor=gray];",
+            start, start, end, end - start + 1).unwrap();
+    }
+
+    writeln!(out, "}}").unwrap();
+
+    out
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut output = None;
+    let mut rom_path = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" => output = Some(iter.next().ok_or(USAGE.to_string())?.clone()),
+            _ if rom_path.is_none() => rom_path = Some(arg.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let rom_path = rom_path.ok_or(USAGE.to_string())?;
+    let chp8_code = fs::read(&rom_path)
+        .map_err(|e| format!("could not read {}: {}", rom_path, e))?;
+
+    let machine = Machine::new(&chp8_code, 0, &Quirks::default())?;
+    let analysis = analyze(&machine);
+    let blocks = basic_blocks(&analysis);
+    let unreachable = unreachable_ranges(&analysis, chp8_code.len());
+    let dot = to_dot(&machine, &analysis, &blocks, &unreachable);
+
+    match &output {
+        Some(path) => fs::write(path, dot).map_err(|e| format!("could not write {}: {}", path, e))?,
+        None => print!("{}", dot),
+    }
+
+    //keep stdout clean for piping the graph into dot
+    eprintln!("{} basic blocks, {} instructions", blocks.len(), analysis.instructions.len());
+    for (start, end) in &unreachable {
+        eprintln!("unreachable {:03x}-{:03x}", start, end);
+    }
+    for (addr, range) in &analysis.tables {
+        match range {
+            Some((lo, hi)) if lo == hi => {
+                let target = (machine.fetch(*addr) & 0xfff) + *lo as u16;
+                eprintln!("indirect jump at {:03x}, V0 is always {} so it goes to {:03x}", addr, lo, target & 0xfff);
+            },
+            Some((lo, hi)) => eprintln!("jump table at {:03x}, V0 {}-{}", addr, lo, hi),
+            None => eprintln!("jump table at {:03x}, V0 unknown so targets weren't followed", addr),
+        }
+    }
+    for (addr, (target, len)) in &analysis.self_modifying {
+        eprintln!("{:03x} may modify code at {:03x}-{:03x}", addr, target, target + len - 1);
+    }
+    for addr in &analysis.invalid {
+        eprintln!("invalid opcode {:04x} at {:03x}", machine.fetch(*addr), addr);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze_rom(chp8_code: &[u8]) -> (Analysis, Vec<(u16, u16)>) {
+        let machine = Machine::new(chp8_code, 0, &Quirks::default()).unwrap();
+        let analysis = analyze(&machine);
+        let unreachable = unreachable_ranges(&analysis, chp8_code.len());
+        (analysis, unreachable)
+    }
+
+    #[test]
+    fn follows_an_odd_v0() {
+        //V0 = 1, jump to 205 + V0 = 206 which loops forever
+        let (analysis, unreachable) = analyze_rom(&[0x60, 0x01, 0xb2, 0x05, 0x00, 0x00, 0x12, 0x06]);
+
+        assert_eq!(analysis.instructions[&0x202], vec![(0x206, EdgeKind::Table(1))]);
+        assert!(analysis.instructions.contains_key(&0x206));
+        assert_eq!(unreachable, vec![(0x204, 0x205)]);
+    }
+
+    #[test]
+    fn follows_every_value_in_a_table() {
+        //V0 = random & 3, jump to 207 + V0, odd values land inside the jumps
+        let (analysis, _) = analyze_rom(&[0xc0, 0x03, 0xb2, 0x07, 0x00, 0x00, 0x12, 0x06, 0x12, 0x08]);
+
+        let targets: Vec<u16> = analysis.instructions[&0x202].iter().map(|(target, _)| *target).collect();
+        assert_eq!(targets, vec![0x207, 0x208, 0x209, 0x20a]);
+        assert_eq!(analysis.tables[&0x202], Some((0, 3)));
+    }
+}
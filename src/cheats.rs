@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::machine::Machine;
+
+//RAM search narrows down which addresses hold a value by comparing memory
+//against the previous snapshot, and cheats then pin those addresses. Cheats
+//are kept in one file per ROM hash so they come back next time the ROM runs.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal(u8),
+    NotEqual(u8),
+    Greater(u8),
+    Less(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    //"eq 5", "changed", "inc" and so on, values are hex like everywhere in the debugger
+    pub fn parse(name: &str, value: Option<&str>) -> Result<Comparison, String> {
+        let value = || -> Result<u8, String> {
+            let text = value.ok_or(format!("'{}' needs a value", name))?;
+            u8::from_str_radix(text.trim_start_matches("0x"), 16)
+                .map_err(|_| format!("bad byte value '{}'", text))
+        };
+
+        match name {
+            "eq" | "=" => Ok(Comparison::Equal(value()?)),
+            "ne" | "!=" => Ok(Comparison::NotEqual(value()?)),
+            "gt" | ">" => Ok(Comparison::Greater(value()?)),
+            "lt" | "<" => Ok(Comparison::Less(value()?)),
+            "changed" | "ch" => Ok(Comparison::Changed),
+            "unchanged" | "un" => Ok(Comparison::Unchanged),
+            "increased" | "inc" => Ok(Comparison::Increased),
+            "decreased" | "dec" => Ok(Comparison::Decreased),
+            _ => Err(format!("unknown comparison '{}'", name)),
+        }
+    }
+
+    fn matches(&self, old: u8, new: u8) -> bool {
+        match *self {
+            Comparison::Equal(value) => new == value,
+            Comparison::NotEqual(value) => new != value,
+            Comparison::Greater(value) => new > value,
+            Comparison::Less(value) => new < value,
+            Comparison::Changed => new != old,
+            Comparison::Unchanged => new == old,
+            Comparison::Increased => new > old,
+            Comparison::Decreased => new < old,
+        }
+    }
+}
+
+pub struct MemorySearch {
+    snapshot: [u8; 4096],
+    pub candidates: Vec<u16>,
+}
+
+impl MemorySearch {
+    //every address is a candidate until the first filter
+    pub fn new(machine: &Machine) -> MemorySearch {
+        MemorySearch {
+            snapshot: machine.memory,
+            candidates: (0..4096).collect(),
+        }
+    }
+
+    //keeps the candidates that match and takes a new snapshot to compare the next filter against
+    pub fn filter(&mut self, machine: &Machine, comparison: Comparison) -> usize {
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|addr| {
+            let addr = *addr as usize;
+            comparison.matches(snapshot[addr], machine.memory[addr])
+        });
+        self.snapshot = machine.memory;
+
+        self.candidates.len()
+    }
+
+    //"2f0=05 2f1=1a ..." for the first few candidates
+    pub fn describe(&self, machine: &Machine, limit: usize) -> String {
+        let mut text: Vec<String> = self.candidates.iter()
+            .take(limit)
+            .map(|addr| format!("{:03x}={:02x}", addr, machine.memory[*addr as usize]))
+            .collect();
+
+        if self.candidates.len() > limit {
+            text.push(format!("and {} more", self.candidates.len() - limit));
+        }
+
+        text.join(" ")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat {
+    pub name: String,
+    pub addr: u16,
+    pub value: u8,
+    //written every frame rather than once
+    pub freeze: bool,
+}
+
+impl Cheat {
+    fn to_line(&self) -> String {
+        format!("{} {:03x} {:02x} {}", self.name, self.addr, self.value,
+            if self.freeze { "freeze" } else { "poke" })
+    }
+
+    fn from_line(line: &str) -> Option<Cheat> {
+        let mut words = line.split_whitespace();
+        let name = words.next()?.to_string();
+        let addr = u16::from_str_radix(words.next()?, 16).ok().filter(|addr| *addr < 4096)?;
+        let value = u8::from_str_radix(words.next()?, 16).ok()?;
+        let freeze = match words.next()? {
+            "freeze" => true,
+            "poke" => false,
+            _ => return None,
+        };
+
+        Some(Cheat { name, addr, value, freeze })
+    }
+
+    fn apply(&self, machine: &mut Machine) {
+        machine.poke(self.addr, self.value);
+    }
+}
+
+pub struct CheatList {
+    //None when cheats can't be saved, like in tools running a ROM without a config
+    path: Option<PathBuf>,
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    //cheats saved for the ROM with this hash, an empty list when there are none yet
+    pub fn load(dir: &Option<PathBuf>, rom_hash: u64) -> Result<CheatList, String> {
+        let path = dir.as_ref().map(|dir| dir.join(format!("{:016x}.cheats", rom_hash)));
+        let mut cheats = Vec::new();
+
+        if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+
+            for (num, line) in contents.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let cheat = Cheat::from_line(line)
+                    .ok_or(format!("{}:{}: expected NAME ADDR VALUE freeze|poke", path.display(), num + 1))?;
+                cheats.push(cheat);
+            }
+        }
+
+        Ok(CheatList { path, cheats })
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+        }
+
+        let contents: String = self.cheats.iter().map(|cheat| cheat.to_line() + "\n").collect();
+        fs::write(path, contents).map_err(|e| format!("could not write {}: {}", path.display(), e))
+    }
+
+    //replaces any cheat with the same name, applying it is up to the caller
+    pub fn add(&mut self, cheat: Cheat) -> Result<(), String> {
+        if cheat.name.contains(char::is_whitespace) {
+            return Err("cheat names can't contain spaces".to_string());
+        }
+
+        self.cheats.retain(|c| c.name != cheat.name);
+        self.cheats.push(cheat);
+        self.save()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<bool, String> {
+        let before = self.cheats.len();
+        self.cheats.retain(|c| c.name != name);
+
+        if self.cheats.len() == before {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    //pokes once when the ROM starts
+    pub fn apply_all(&self, machine: &mut Machine) {
+        for cheat in &self.cheats {
+            cheat.apply(machine);
+        }
+    }
+
+    //the cheats written again every frame
+    pub fn frozen(&self) -> impl Iterator<Item = &Cheat> {
+        self.cheats.iter().filter(|c| c.freeze)
+    }
+}
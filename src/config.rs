@@ -0,0 +1,363 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::coverage::CoverageConfig;
+use crate::display::{DisplayConfig, Palette};
+use crate::governor::SpeedConfig;
+use crate::phosphor::PhosphorMode;
+use crate::profiler::ProfileConfig;
+use crate::machine;
+use crate::quirks::{FontSet, Quirks};
+use crate::recompiler::Backend;
+use crate::rewind::RewindConfig;
+use crate::screenshot::{ScreenshotConfig, ScreenshotFormat};
+use crate::terminal::Glyphs;
+use crate::timing::Timing;
+use crate::trace::{self, TraceConfig, TraceFormat};
+
+const USAGE: &str = "usage: chip8 [--config FILE] [--scale N] [--fullscreen] \
+[--palette NAME] [--colors HEX,HEX[,HEX,HEX]] \
+[--phosphor off|low|medium|high|blend:F|or:N] \
+[--frontend sdl|terminal|headless] [--frames N] [--glyphs halfblock|braille] \
+[--screenshot-dir DIR] [--screenshot-format png|pbm|both] [--screenshot-at FRAME] \
+[--record FILE.gif|DIR] [--record-input FILE] [--replay FILE] [--seed N] \
+[--rewind-frames N] [--rewind-memory MB] [--history N] \
+[--trace FILE] [--trace-format text|binary] [--trace-range LO-HI] [--trace-ring N] \
+[--gdb PORT|HOST:PORT|unix:PATH] [--profile FILE] [--profile-folded FILE] \
+[--coverage FILE] [--coverage-lcov FILE] [--coverage-map FILE] [--cheat-dir DIR] \
+[--quirks vip|chip48|schip|octo] [--font vip|chip48|schip|octo] [--font-address HEX] \
+[--timing fixed|vip] [--vblank-wait on|off] [--ipf N] [--fast-forward N] [--benchmark] [--backend interpreter|recompiler] <rom>";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Frontend {
+    #[default]
+    Sdl,
+    Terminal,
+    Headless,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub rom_path: String,
+    pub display: DisplayConfig,
+    pub frontend: Frontend,
+    pub glyphs: Glyphs,
+    pub screenshot: ScreenshotConfig,
+    //GIF file or PNG sequence directory to record from the first frame
+    pub record: Option<PathBuf>,
+    //stop after this many frames, only used by the headless frontend
+    pub frames: Option<u64>,
+    //movie file to save the keypad input of every frame to
+    pub record_input: Option<PathBuf>,
+    //movie file to take the keypad input from
+    pub replay: Option<PathBuf>,
+    //random number seed for Cxkk, picked at random when not set
+    pub seed: Option<u64>,
+    pub rewind: RewindConfig,
+    //instructions the debugger can step back through
+    pub history: usize,
+    pub trace: TraceConfig,
+    //serve the GDB remote protocol here instead of running a frontend
+    pub gdb: Option<String>,
+    pub profile: ProfileConfig,
+    pub coverage: CoverageConfig,
+    //where cheats are saved, one file per ROM hash
+    pub cheat_dir: Option<PathBuf>,
+    pub quirks: Quirks,
+    pub speed: SpeedConfig,
+    pub backend: Backend,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            rom_path: String::new(),
+            display: DisplayConfig::default(),
+            frontend: Frontend::default(),
+            glyphs: Glyphs::default(),
+            screenshot: ScreenshotConfig::default(),
+            record: None,
+            frames: None,
+            record_input: None,
+            replay: None,
+            seed: None,
+            rewind: RewindConfig::default(),
+            history: 50_000,
+            trace: TraceConfig::default(),
+            gdb: None,
+            profile: ProfileConfig::default(),
+            coverage: CoverageConfig::default(),
+            cheat_dir: Some("cheats".into()),
+            quirks: Quirks::default(),
+            speed: SpeedConfig::default(),
+            backend: Backend::default(),
+        }
+    }
+}
+
+impl Config {
+    //shared by the config file and the command line so both accept the same keys
+    fn apply(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "scale" => {
+                let scale: u32 = value.parse()
+                    .map_err(|_| format!("bad scale '{}'", value))?;
+                if scale == 0 {
+                    return Err("scale must be at least 1".to_string());
+                }
+                self.display.scale = scale;
+            },
+            "fullscreen" => {
+                self.display.fullscreen = parse_bool(value)?;
+            },
+            "palette" => {
+                self.display.palette = Palette::from_name(value)
+                    .ok_or(format!("unknown palette '{}'", value))?;
+            },
+            "colors" => {
+                self.display.palette = Palette::from_hex_list(value)?;
+            },
+            "phosphor" => {
+                self.display.phosphor = PhosphorMode::parse(value)?;
+            },
+            "frontend" => {
+                self.frontend = match value {
+                    "sdl" => Frontend::Sdl,
+                    "terminal" => Frontend::Terminal,
+                    "headless" => Frontend::Headless,
+                    _ => return Err(format!("unknown frontend '{}'", value)),
+                };
+            },
+            "glyphs" => {
+                self.glyphs = match value {
+                    "halfblock" => Glyphs::HalfBlock,
+                    "braille" => Glyphs::Braille,
+                    _ => return Err(format!("unknown glyphs '{}'", value)),
+                };
+            },
+            "screenshot-dir" => {
+                self.screenshot.dir = value.into();
+            },
+            "screenshot-format" => {
+                self.screenshot.format = ScreenshotFormat::parse(value)?;
+            },
+            "screenshot-at" => {
+                let frame: u64 = value.parse()
+                    .map_err(|_| format!("bad frame number '{}'", value))?;
+                self.screenshot.at_frame = Some(frame);
+            },
+            "record" => {
+                self.record = Some(value.into());
+            },
+            "frames" => {
+                let frames: u64 = value.parse()
+                    .map_err(|_| format!("bad frame count '{}'", value))?;
+                self.frames = Some(frames);
+            },
+            "record-input" => {
+                self.record_input = Some(value.into());
+            },
+            "replay" => {
+                self.replay = Some(value.into());
+            },
+            "seed" => {
+                let seed: u64 = value.parse()
+                    .map_err(|_| format!("bad seed '{}'", value))?;
+                self.seed = Some(seed);
+            },
+            "rewind-frames" => {
+                self.rewind.frames = value.parse()
+                    .map_err(|_| format!("bad frame count '{}'", value))?;
+            },
+            "rewind-memory" => {
+                let megabytes: usize = value.parse()
+                    .map_err(|_| format!("bad rewind memory '{}'", value))?;
+                self.rewind.memory_budget = megabytes * 1024 * 1024;
+            },
+            "history" => {
+                self.history = value.parse()
+                    .map_err(|_| format!("bad history length '{}'", value))?;
+            },
+            "trace" => {
+                self.trace.path = Some(value.into());
+            },
+            "trace-format" => {
+                self.trace.format = TraceFormat::parse(value)?;
+            },
+            "trace-range" => {
+                self.trace.range = Some(trace::parse_range(value)?);
+            },
+            "trace-ring" => {
+                let size: usize = value.parse()
+                    .map_err(|_| format!("bad trace ring size '{}'", value))?;
+                if size == 0 {
+                    return Err("trace ring size must be at least 1".to_string());
+                }
+                self.trace.ring = Some(size);
+            },
+            "gdb" => {
+                self.gdb = Some(value.to_string());
+            },
+            "profile" => {
+                self.profile.report = Some(value.into());
+            },
+            "profile-folded" => {
+                self.profile.folded = Some(value.into());
+            },
+            "coverage" => {
+                self.coverage.listing = Some(value.into());
+            },
+            "coverage-lcov" => {
+                self.coverage.lcov = Some(value.into());
+            },
+            "coverage-map" => {
+                self.coverage.map = Some(value.into());
+            },
+            "cheat-dir" => {
+                self.cheat_dir = Some(value.into());
+            },
+            "quirks" => {
+                self.quirks = Quirks::from_profile(value)
+                    .ok_or(format!("unknown profile '{}'", value))?;
+            },
+            "font" => {
+                self.quirks.font = FontSet::from_name(value)
+                    .ok_or(format!("unknown font set '{}'", value))?;
+            },
+            "font-address" => {
+                let address = u16::from_str_radix(value.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("bad font address '{}'", value))?;
+                machine::check_font_address(address)?;
+                self.quirks.font_address = address;
+            },
+            "timing" => {
+                self.quirks.timing = Timing::parse(value)?;
+            },
+            "vblank-wait" => {
+                self.quirks.vblank_wait = parse_bool(value)?;
+            },
+            "ipf" => {
+                let ipf: usize = value.parse()
+                    .map_err(|_| format!("bad instructions per frame '{}'", value))?;
+                if ipf == 0 {
+                    return Err("instructions per frame must be at least 1".to_string());
+                }
+                self.quirks.instructions_per_frame = ipf;
+            },
+            "fast-forward" => {
+                let multiplier: u32 = value.parse()
+                    .map_err(|_| format!("bad fast forward multiplier '{}'", value))?;
+                if multiplier == 0 {
+                    return Err("fast forward multiplier must be at least 1".to_string());
+                }
+                self.speed.fast_forward = multiplier;
+            },
+            "benchmark" => {
+                self.speed.benchmark = parse_bool(value)?;
+            },
+            "backend" => {
+                self.backend = Backend::parse(value)?;
+            },
+            _ => return Err(format!("unknown option '{}'", key)),
+        }
+
+        Ok(())
+    }
+
+    //a quirks profile replaces every quirk, so profiles go first and the
+    //single quirk options given with them still apply on top
+    pub fn apply_all(&mut self, options: &[(String, String)]) -> Result<(), String> {
+        let (profiles, others): (Vec<_>, Vec<_>) = options.iter().partition(|(key, _)| key == "quirks");
+        for (key, value) in profiles.into_iter().chain(others) {
+            self.apply(key, value)?;
+        }
+
+        Ok(())
+    }
+
+    //applies only the quirks profile, or everything but it
+    fn load_file(&mut self, path: &str, profiles: bool) -> Result<(), String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("could not read config {}: {}", path, e))?;
+
+        for (num, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .ok_or(format!("{}:{}: expected key = value", path, num + 1))?;
+
+            let key = key.trim();
+            if (key == "quirks") != profiles {
+                continue;
+            }
+            self.apply(key, value.trim())
+                .map_err(|e| format!("{}:{}: {}", path, num + 1, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!("expected true or false, got '{}'", value)),
+    }
+}
+
+//flags that don't take a value
+fn is_switch(key: &str) -> bool {
+    key == "fullscreen" || key == "benchmark"
+}
+
+pub fn parse_args(args: &[String]) -> Result<Config, String> {
+    let mut config = Config::default();
+    let config_path = match args.iter().position(|a| a == "--config") {
+        Some(pos) => Some(args.get(pos + 1).ok_or(USAGE.to_string())?),
+        None => None,
+    };
+
+    let mut rom_path = None;
+
+    //a quirks profile replaces every quirk, so profiles are applied in a
+    //first pass and the single quirk options go on top wherever they were given
+    for profiles in [true, false] {
+        //config file goes first so command line flags override it
+        if let Some(path) = config_path {
+            config.load_file(path, profiles)?;
+        }
+
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            if let Some(key) = arg.strip_prefix("--") {
+                if key == "config" {
+                    iter.next();
+                    continue;
+                }
+
+                let value = if is_switch(key) { "true" } else { iter.next().ok_or(USAGE.to_string())? };
+                if (key == "quirks") == profiles {
+                    config.apply(key, value)?;
+                }
+            } else if profiles {
+                continue;
+            } else if rom_path.is_none() {
+                rom_path = Some(arg.clone());
+            } else {
+                return Err(USAGE.to_string());
+            }
+        }
+    }
+
+    config.rom_path = rom_path.ok_or(USAGE.to_string())?;
+    //the map only says which source lines the lcov output points at
+    if config.coverage.map.is_some() && config.coverage.lcov.is_none() {
+        return Err("--coverage-map needs --coverage-lcov".to_string());
+    }
+
+    Ok(config)
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::disasm;
+use crate::machine::Machine;
+
+//source line -> (hits, taken and not taken counts of each skip on the line)
+type LineHits = BTreeMap<usize, (u64, Vec<(u64, u64)>)>;
+
+//Which ROM instructions ran and, for the skip opcodes, which way they went.
+//The annotated disassembly marks lines that never ran with ##### like gcov.
+//The lcov file points at the disassembly, or at the assembler's sources when
+//a map file says which source line each address came from.
+
+#[derive(Clone, Debug, Default)]
+pub struct CoverageConfig {
+    //annotated disassembly
+    pub listing: Option<PathBuf>,
+    pub lcov: Option<PathBuf>,
+    //"ADDR FILE:LINE" per line, ADDR in hex
+    pub map: Option<PathBuf>,
+}
+
+impl CoverageConfig {
+    pub fn enabled(&self) -> bool {
+        self.listing.is_some() || self.lcov.is_some()
+    }
+}
+
+//where each address's instruction came from in the assembler's sources
+fn load_map(path: &PathBuf) -> Result<BTreeMap<u16, (String, usize)>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let mut map = BTreeMap::new();
+
+    for (num, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let bad_line = || format!("{}:{}: expected ADDR FILE:LINE", path.display(), num + 1);
+        let (addr, location) = line.split_once(char::is_whitespace).ok_or_else(bad_line)?;
+        let (file, source_line) = location.trim().rsplit_once(':').ok_or_else(bad_line)?;
+
+        let addr = u16::from_str_radix(addr.trim_start_matches("0x"), 16).map_err(|_| bad_line())?;
+        let source_line = source_line.parse().map_err(|_| bad_line())?;
+        map.insert(addr, (file.to_string(), source_line));
+    }
+
+    Ok(map)
+}
+
+struct ListingLine {
+    addr: u16,
+    //a lone byte before an instruction at an odd address
+    single_byte: bool,
+}
+
+pub struct Coverage {
+    config: CoverageConfig,
+    rom_path: String,
+    rom_end: usize,
+    executions: Vec<u64>,
+    //times each skip was taken and not taken
+    skips: Vec<(u64, u64)>,
+    //skip waiting for its outcome, set between before_step and after_step
+    pending: Option<u16>,
+}
+
+impl Coverage {
+    pub fn new(config: &CoverageConfig, rom_path: &str, rom_len: usize) -> Coverage {
+        Coverage {
+            config: config.clone(),
+            rom_path: rom_path.to_string(),
+            rom_end: (0x200 + rom_len).min(4096),
+            executions: vec![0; 4096],
+            skips: vec![(0, 0); 4096],
+            pending: None,
+        }
+    }
+
+    pub fn before_step(&mut self, machine: &Machine) {
+        let pc = machine.reg.PC;
+        self.executions[pc as usize % 4096] += 1;
+        self.pending = disasm::is_skip(machine.fetch(pc)).then_some(pc);
+    }
+
+    pub fn after_step(&mut self, machine: &Machine) {
+        if let Some(pc) = self.pending.take() {
+            let skip = &mut self.skips[pc as usize % 4096];
+            if machine.reg.PC == pc.wrapping_add(4) {
+                skip.0 += 1;
+            } else {
+                skip.1 += 1;
+            }
+        }
+    }
+
+    //instructions are normally two byte aligned but a jump can land on an odd
+    //address, so a byte is listed on its own when only the next one ran
+    fn listing_lines(&self) -> Vec<ListingLine> {
+        let mut lines = Vec::new();
+        let mut addr = 0x200;
+
+        while addr < self.rom_end {
+            let single_byte = self.executions[addr] == 0
+                && addr + 1 < self.rom_end && self.executions[addr + 1] > 0;
+            lines.push(ListingLine { addr: addr as u16, single_byte });
+            addr += if single_byte { 1 } else { 2 };
+        }
+
+        lines
+    }
+
+    fn listing(&self, machine: &Machine, lines: &[ListingLine]) -> String {
+        let mut out = String::new();
+
+        for line in lines {
+            let addr = line.addr as usize;
+            let count = self.executions[addr];
+            let hits = if count > 0 { count.to_string() } else { "#####".to_string() };
+
+            if line.single_byte {
+                writeln!(out, "{:>10}  {:03x}  {:02x}    DB 0x{:02X}",
+                    "-", addr, machine.memory[addr], machine.memory[addr]).unwrap();
+                continue;
+            }
+
+            let instruction = machine.fetch(line.addr);
+            write!(out, "{:>10}  {:03x}  {:04x}  {}",
+                hits, addr, instruction, disasm::mnemonic(instruction)).unwrap();
+
+            if disasm::is_skip(instruction) && count > 0 {
+                let (taken, not_taken) = self.skips[addr];
+                write!(out, "  ; skip taken {}, not taken {}", taken, not_taken).unwrap();
+                if taken == 0 || not_taken == 0 {
+                    write!(out, "  <<<<<").unwrap();
+                }
+            }
+            writeln!(out).unwrap();
+        }
+
+        let (total, hit) = self.instruction_counts(lines);
+        let (branches, branches_hit) = self.branch_counts(lines, machine);
+        writeln!(out).unwrap();
+        writeln!(out, "{} of {} instructions executed, {} of {} skip outcomes seen",
+            hit, total, branches_hit, branches).unwrap();
+
+        out
+    }
+
+    fn instruction_counts(&self, lines: &[ListingLine]) -> (usize, usize) {
+        let instructions = lines.iter().filter(|line| !line.single_byte);
+        let hit = instructions.clone().filter(|line| self.executions[line.addr as usize] > 0).count();
+        (instructions.count(), hit)
+    }
+
+    //every skip counts as two branches
+    fn branch_counts(&self, lines: &[ListingLine], machine: &Machine) -> (usize, usize) {
+        let mut total = 0;
+        let mut hit = 0;
+
+        for line in lines.iter().filter(|line| !line.single_byte) {
+            if disasm::is_skip(machine.fetch(line.addr)) {
+                let (taken, not_taken) = self.skips[line.addr as usize];
+                total += 2;
+                hit += (taken > 0) as usize + (not_taken > 0) as usize;
+            }
+        }
+
+        (total, hit)
+    }
+
+    fn lcov(&self, machine: &Machine, lines: &[ListingLine]) -> Result<String, String> {
+        //without a map the line numbers are the annotated disassembly's
+        let map = match &self.config.map {
+            Some(path) => load_map(path)?,
+            None => {
+                let file = self.config.listing.as_ref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| self.rom_path.clone());
+                lines.iter().enumerate()
+                    .map(|(num, line)| (line.addr, (file.clone(), num + 1)))
+                    .collect()
+            },
+        };
+
+        let mut files: BTreeMap<String, LineHits> = BTreeMap::new();
+
+        for line in lines.iter().filter(|line| !line.single_byte) {
+            let (file, source_line) = match map.get(&line.addr) {
+                Some(location) => location.clone(),
+                None => continue,
+            };
+
+            let entry = files.entry(file).or_default().entry(source_line).or_insert((0, Vec::new()));
+            entry.0 += self.executions[line.addr as usize];
+            if disasm::is_skip(machine.fetch(line.addr)) {
+                entry.1.push(self.skips[line.addr as usize]);
+            }
+        }
+
+        let mut out = String::new();
+        writeln!(out, "TN:").unwrap();
+
+        for (file, source_lines) in &files {
+            writeln!(out, "SF:{}", file).unwrap();
+
+            let mut branches = 0;
+            let mut branches_hit = 0;
+            for (source_line, (hits, skips)) in source_lines {
+                for (block, (taken, not_taken)) in skips.iter().enumerate() {
+                    for (branch, count) in [taken, not_taken].iter().enumerate() {
+                        //"-" means the skip itself never ran
+                        let count = if *hits == 0 { "-".to_string() } else { count.to_string() };
+                        writeln!(out, "BRDA:{},{},{},{}", source_line, block, branch, count).unwrap();
+                    }
+                    branches += 2;
+                    branches_hit += (*taken > 0) as usize + (*not_taken > 0) as usize;
+                }
+            }
+            writeln!(out, "BRF:{}", branches).unwrap();
+            writeln!(out, "BRH:{}", branches_hit).unwrap();
+
+            for (source_line, (hits, _)) in source_lines {
+                writeln!(out, "DA:{},{}", source_line, hits).unwrap();
+            }
+            writeln!(out, "LF:{}", source_lines.len()).unwrap();
+            writeln!(out, "LH:{}", source_lines.values().filter(|(hits, _)| *hits > 0).count()).unwrap();
+            writeln!(out, "end_of_record").unwrap();
+        }
+
+        Ok(out)
+    }
+
+    pub fn finish(&self, machine: &Machine) -> Result<(), String> {
+        let lines = self.listing_lines();
+
+        if let Some(path) = &self.config.listing {
+            fs::write(path, self.listing(machine, &lines))
+                .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        }
+
+        if let Some(path) = &self.config.lcov {
+            fs::write(path, self.lcov(machine, &lines)?)
+                .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent, KeyEventKind},
+    layout::Rect,
+    style::Stylize,
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph},
+    Frame,
+};
+
+use crate::cheats::{Cheat, Comparison, MemorySearch};
+use crate::machine::Machine;
+use crate::session::Session;
+
+const HELP: &str = "F5 run/pause  F10 step  F7 step back  : command";
+const COMMANDS: &str = "s [n], rs [n], c, p, rc [addr | v0-vf | i | sp | dt | st | [addr]], \
+search [eq|ne|gt|lt VALUE | changed | unchanged | inc | dec], \
+freeze NAME ADDR VALUE, poke NAME ADDR VALUE, cheats, uncheat NAME";
+
+//RAM search candidates listed in the debug view
+const SEARCH_SHOWN: usize = 8;
+
+//what reverse-continue stops at
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Watch {
+    //PC reaching this address
+    Address(u16),
+    //a register changing, named as in the debug view
+    Register(&'static str),
+    Memory(u16),
+}
+
+const REGISTER_NAMES: [&str; 20] = [
+    "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7",
+    "v8", "v9", "va", "vb", "vc", "vd", "ve", "vf",
+    "i", "sp", "dt", "st",
+];
+
+fn register_value(machine: &Machine, name: &str) -> u16 {
+    let reg = &machine.reg;
+    match name {
+        "i" => reg.I,
+        "sp" => reg.SP,
+        "dt" => reg.DT as u16,
+        "st" => reg.ST as u16,
+        _ => reg.V[usize::from_str_radix(&name[1..], 16).unwrap()] as u16,
+    }
+}
+
+fn parse_hex(text: &str) -> Result<u16, String> {
+    let text = text.trim_start_matches("0x");
+    u16::from_str_radix(text, 16).map_err(|_| format!("bad address '{}'", text))
+}
+
+fn parse_watch(text: &str) -> Result<Watch, String> {
+    let text = text.to_ascii_lowercase();
+
+    if let Some(name) = REGISTER_NAMES.iter().find(|&&name| name == text) {
+        return Ok(Watch::Register(name));
+    }
+
+    if text.starts_with('[') && text.ends_with(']') {
+        return Ok(Watch::Memory(parse_hex(&text[1..text.len() - 1])?));
+    }
+
+    Ok(Watch::Address(parse_hex(&text)?))
+}
+
+//pause, single step and step back through the session's instruction history
+pub struct Debugger {
+    pub paused: bool,
+    prompt: Option<String>,
+    message: String,
+    search: Option<MemorySearch>,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            paused: false,
+            prompt: None,
+            message: String::new(),
+            search: None,
+        }
+    }
+
+    //true when the key was meant for the debugger and shouldn't reach the game
+    pub fn handle_key(&mut self, key: &KeyEvent, session: &mut Session) -> Result<bool, String> {
+        if key.kind == KeyEventKind::Release {
+            return Ok(self.prompt.is_some());
+        }
+
+        if let Some(prompt) = &mut self.prompt {
+            match key.code {
+                KeyCode::Char(c) => prompt.push(c),
+                KeyCode::Backspace => {
+                    prompt.pop();
+                },
+                KeyCode::Enter => {
+                    let command = prompt.clone();
+                    self.prompt = None;
+                    self.message = match self.run_command(&command, session) {
+                        Ok(message) => message,
+                        Err(e) => e,
+                    };
+                },
+                KeyCode::Esc => self.prompt = None,
+                _ => {},
+            }
+            return Ok(true);
+        }
+
+        let result = match key.code {
+            KeyCode::Char(':') => {
+                self.prompt = Some(String::new());
+                return Ok(true);
+            },
+            KeyCode::F(5) => self.run_command(if self.paused { "c" } else { "p" }, session),
+            KeyCode::F(10) => self.run_command("s", session),
+            KeyCode::F(7) => self.run_command("rs", session),
+            _ => return Ok(false),
+        };
+
+        self.message = match result {
+            Ok(message) => message,
+            Err(e) => e,
+        };
+
+        Ok(true)
+    }
+
+    fn run_command(&mut self, command: &str, session: &mut Session) -> Result<String, String> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let arguments: Vec<&str> = words.collect();
+        let argument = arguments.first().copied();
+
+        let count = || -> Result<usize, String> {
+            match argument {
+                Some(n) => n.parse().map_err(|_| format!("bad count '{}'", n)),
+                None => Ok(1),
+            }
+        };
+
+        match name {
+            "c" | "continue" => {
+                self.paused = false;
+                Ok("running".to_string())
+            },
+            "p" | "pause" => {
+                self.paused = true;
+                Ok("paused".to_string())
+            },
+            "s" | "step" => {
+                self.paused = true;
+                for _ in 0..count()? {
+                    let key = session.machine.held_key();
+                    session.step_instruction(key)?;
+                }
+                Ok(format!("stepped to {:03x}", session.machine.reg.PC))
+            },
+            "rs" | "rstep" => {
+                self.paused = true;
+                for _ in 0..count()? {
+                    if !session.step_back()? {
+                        return Ok("start of history".to_string());
+                    }
+                }
+                Ok(format!("stepped back to {:03x}", session.machine.reg.PC))
+            },
+            "rc" | "rcontinue" => {
+                self.paused = true;
+                let watch = match argument {
+                    Some(text) => Some(parse_watch(text)?),
+                    None => None,
+                };
+                reverse_continue(session, watch)
+            },
+            "search" => self.search(&arguments, session),
+            "freeze" | "poke" => {
+                let (cheat_name, addr, value) = match arguments[..] {
+                    [cheat_name, addr, value] => (cheat_name, addr, value),
+                    _ => return Err(format!("usage: {} NAME ADDR VALUE", name)),
+                };
+                let addr = parse_hex(addr)?;
+                if addr >= 4096 {
+                    return Err(format!("address {:x} is outside memory", addr));
+                }
+                let value = u8::from_str_radix(value.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("bad byte value '{}'", value))?;
+
+                let cheat = Cheat {
+                    name: cheat_name.to_string(),
+                    addr,
+                    value,
+                    freeze: name == "freeze",
+                };
+                session.add_cheat(cheat)?;
+                Ok(format!("{} {:03x} = {:02x}", cheat_name, addr, value))
+            },
+            "uncheat" => {
+                let name = argument.ok_or("usage: uncheat NAME")?;
+                match session.cheats.remove(name)? {
+                    true => Ok(format!("removed {}", name)),
+                    false => Err(format!("no cheat named {}", name)),
+                }
+            },
+            "cheats" => {
+                let cheats: Vec<String> = session.cheats.cheats.iter()
+                    .map(|c| format!("{} {:03x}={:02x}{}", c.name, c.addr, c.value,
+                        if c.freeze { " frozen" } else { "" }))
+                    .collect();
+                Ok(if cheats.is_empty() { "no cheats".to_string() } else { cheats.join(", ") })
+            },
+            _ => Err(format!("unknown command '{}', try {}", command, COMMANDS)),
+        }
+    }
+
+    //no arguments starts a new search, otherwise the candidates are filtered
+    fn search(&mut self, arguments: &[&str], session: &Session) -> Result<String, String> {
+        let machine = &session.machine;
+
+        let comparison = match arguments.first() {
+            Some(name) => Comparison::parse(name, arguments.get(1).copied())?,
+            None => {
+                self.search = Some(MemorySearch::new(machine));
+                return Ok("new search, all of memory is a candidate".to_string());
+            },
+        };
+
+        let search = self.search.as_mut().ok_or("start a search first with 'search'")?;
+        let count = search.filter(machine, comparison);
+
+        Ok(format!("{} candidates: {}", count, search.describe(machine, SEARCH_SHOWN)))
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, session: &Session) {
+        render_lines(frame, area, self.lines(session));
+    }
+
+    //what the debug view shows, owned so it can be drawn somewhere else
+    pub fn lines(&self, session: &Session) -> Vec<Line<'static>> {
+        let machine = &session.machine;
+        let reg = &machine.reg;
+        let pc = reg.PC as usize % 4096;
+
+        let mut lines = vec![
+            Line::from(format!("{}  cycle {}  frame {}  history {}",
+                if self.paused { "PAUSED" } else { "RUNNING" },
+                machine.cycle, machine.frame, session.history_len())),
+            Line::from(format!("PC {:03x} [{:02x}{:02x}]  I {:03x}  SP {:03x}  DT {:02x}  ST {:02x}",
+                reg.PC, machine.memory[pc], machine.memory[(pc + 1) % 4096],
+                reg.I, reg.SP, reg.DT, reg.ST)),
+        ];
+
+        for row in 0..4 {
+            let text: Vec<String> = (0..4)
+                .map(|col| row * 4 + col)
+                .map(|i| format!("V{:X} {:02x}", i, reg.V[i]))
+                .collect();
+            lines.push(Line::from(text.join("  ")));
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::from(self.message.clone()));
+        lines.push(match &self.prompt {
+            Some(prompt) => Line::from(format!(":{}_", prompt)),
+            None => Line::from(HELP),
+        });
+
+        lines
+    }
+}
+
+pub fn render_lines(frame: &mut Frame, area: Rect, lines: Vec<Line>) {
+    let title = Line::from(" CHIP-8 DEBUG ".bold()).centered();
+    let block = Block::bordered()
+        .title_top(title)
+        .border_set(border::THICK);
+
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn reverse_continue(session: &mut Session, watch: Option<Watch>) -> Result<String, String> {
+    let start = match watch {
+        Some(Watch::Register(name)) => register_value(&session.machine, name),
+        Some(Watch::Memory(addr)) => session.machine.memory[addr as usize % 4096] as u16,
+        _ => 0,
+    };
+
+    let mut steps = 0;
+    while session.step_back()? {
+        steps += 1;
+
+        let machine = &session.machine;
+        let hit = match watch {
+            Some(Watch::Address(addr)) => machine.reg.PC == addr,
+            Some(Watch::Register(name)) => register_value(machine, name) != start,
+            Some(Watch::Memory(addr)) => machine.memory[addr as usize % 4096] as u16 != start,
+            None => false,
+        };
+
+        if hit {
+            return Ok(format!("stopped at {:03x} after {} steps back", machine.reg.PC, steps));
+        }
+    }
+
+    Ok(format!("start of history after {} steps back", steps))
+}
@@ -0,0 +1,80 @@
+//Instructions split into their fields once and kept by address, so running
+//the same loop again skips reading memory and pulling the nibbles apart.
+//Anything that writes memory has to tell the cache, the machine does this for
+//its own writes and everything else goes through Machine::poke.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decoded {
+    pub instruction: u16,
+    pub opcode: u8,
+    pub nnn: u16,
+    pub x: u8,
+    pub y: u8,
+    pub kk: u8,
+    pub n: u8,
+}
+
+impl Decoded {
+    pub fn new(instruction: u16) -> Decoded {
+        Decoded {
+            instruction,
+            opcode: (instruction >> 12) as u8,
+            nnn: instruction & 0x0fff,
+            x: ((instruction >> 8) & 0xf) as u8,
+            y: ((instruction >> 4) & 0xf) as u8,
+            kk: instruction as u8,
+            n: (instruction & 0xf) as u8,
+        }
+    }
+}
+
+pub struct DecodeCache {
+    //one slot per address, instructions can start on odd addresses too
+    entries: Vec<Option<Decoded>>,
+    //off decodes every time, for comparing against in benchmarks
+    enabled: bool,
+    //goes up with every write to memory, so others holding on to code can
+    //tell when it might have changed
+    generation: u64,
+}
+
+impl DecodeCache {
+    pub fn new(enabled: bool) -> DecodeCache {
+        DecodeCache {
+            entries: vec![None; 4096],
+            enabled,
+            generation: 0,
+        }
+    }
+
+    pub fn get(&mut self, memory: &[u8; 4096], addr: u16) -> Decoded {
+        let addr = addr as usize % 4096;
+
+        if let Some(decoded) = self.entries[addr] {
+            return decoded;
+        }
+
+        let decoded = Decoded::new(((memory[addr] as u16) << 8) | memory[(addr + 1) % 4096] as u16);
+        if self.enabled {
+            self.entries[addr] = Some(decoded);
+        }
+        decoded
+    }
+
+    //a byte is part of the instruction starting on it and the one before it
+    pub fn invalidate(&mut self, addr: u16) {
+        let addr = addr as usize % 4096;
+        self.entries[addr] = None;
+        self.entries[(addr + 4095) % 4096] = None;
+        self.generation += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.generation += 1;
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
@@ -0,0 +1,58 @@
+//Cowgod style mnemonic for one instruction, anything unknown is shown as data
+pub fn mnemonic(instruction: u16) -> String {
+    let opcode = instruction >> 12;
+    let var_nnn = instruction & 0x0fff;
+    let var_x = (instruction >> 8) & 0xf;
+    let var_y = (instruction >> 4) & 0xf;
+    let var_kk = instruction & 0xff;
+    let var_z = instruction & 0xf;
+
+    match (opcode, var_x, var_y, var_z) {
+        (0x0, 0x0, 0xe, 0x0) => "CLS".to_string(),
+        (0x0, 0x0, 0xe, 0xe) => "RET".to_string(),
+        (0x0, _, _, _) => format!("SYS 0x{:03X}", var_nnn),
+        (0x1, _, _, _) => format!("JP 0x{:03X}", var_nnn),
+        (0x2, _, _, _) => format!("CALL 0x{:03X}", var_nnn),
+        (0x3, _, _, _) => format!("SE V{:X}, 0x{:02X}", var_x, var_kk),
+        (0x4, _, _, _) => format!("SNE V{:X}, 0x{:02X}", var_x, var_kk),
+        (0x5, _, _, 0x0) => format!("SE V{:X}, V{:X}", var_x, var_y),
+        (0x6, _, _, _) => format!("LD V{:X}, 0x{:02X}", var_x, var_kk),
+        (0x7, _, _, _) => format!("ADD V{:X}, 0x{:02X}", var_x, var_kk),
+        (0x8, _, _, 0x0) => format!("LD V{:X}, V{:X}", var_x, var_y),
+        (0x8, _, _, 0x1) => format!("OR V{:X}, V{:X}", var_x, var_y),
+        (0x8, _, _, 0x2) => format!("AND V{:X}, V{:X}", var_x, var_y),
+        (0x8, _, _, 0x3) => format!("XOR V{:X}, V{:X}", var_x, var_y),
+        (0x8, _, _, 0x4) => format!("ADD V{:X}, V{:X}", var_x, var_y),
+        (0x8, _, _, 0x5) => format!("SUB V{:X}, V{:X}", var_x, var_y),
+        (0x8, _, _, 0x6) => format!("SHR V{:X}, V{:X}", var_x, var_y),
+        (0x8, _, _, 0x7) => format!("SUBN V{:X}, V{:X}", var_x, var_y),
+        (0x8, _, _, 0xe) => format!("SHL V{:X}, V{:X}", var_x, var_y),
+        (0x9, _, _, 0x0) => format!("SNE V{:X}, V{:X}", var_x, var_y),
+        (0xa, _, _, _) => format!("LD I, 0x{:03X}", var_nnn),
+        (0xb, _, _, _) => format!("JP V0, 0x{:03X}", var_nnn),
+        (0xc, _, _, _) => format!("RND V{:X}, 0x{:02X}", var_x, var_kk),
+        (0xd, _, _, _) => format!("DRW V{:X}, V{:X}, {}", var_x, var_y, var_z),
+        (0xe, _, 0x9, 0xe) => format!("SKP V{:X}", var_x),
+        (0xe, _, 0xa, 0x1) => format!("SKNP V{:X}", var_x),
+        (0xf, _, 0x0, 0x7) => format!("LD V{:X}, DT", var_x),
+        (0xf, _, 0x0, 0xa) => format!("LD V{:X}, K", var_x),
+        (0xf, _, 0x1, 0x5) => format!("LD DT, V{:X}", var_x),
+        (0xf, _, 0x1, 0x8) => format!("LD ST, V{:X}", var_x),
+        (0xf, _, 0x1, 0xe) => format!("ADD I, V{:X}", var_x),
+        (0xf, _, 0x2, 0x9) => format!("LD F, V{:X}", var_x),
+        (0xf, _, 0x3, 0x3) => format!("LD B, V{:X}", var_x),
+        (0xf, _, 0x5, 0x5) => format!("LD [I], V{:X}", var_x),
+        (0xf, _, 0x6, 0x5) => format!("LD V{:X}, [I]", var_x),
+        _ => format!("DW 0x{:04X}", instruction),
+    }
+}
+
+//instructions that skip the next one when their condition holds
+pub fn is_skip(instruction: u16) -> bool {
+    match (instruction >> 12, instruction & 0xff) {
+        (0x3, _) | (0x4, _) => true,
+        (0x5, kk) | (0x9, kk) => kk & 0xf == 0,
+        (0xe, 0x9e) | (0xe, 0xa1) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,127 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+
+use crate::phosphor::{Intensity, PhosphorMode};
+
+pub use crate::machine::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
+
+//XO-CHIP palettes have 4 entries: background, plane 1, plane 2, both planes.
+//plain CHIP-8 only ever uses the first two.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: [Color; 4],
+}
+
+impl Palette {
+    pub fn background(&self) -> Color {
+        self.colors[0]
+    }
+
+    pub fn foreground(&self) -> Color {
+        self.colors[1]
+    }
+
+    pub fn from_name(name: &str) -> Option<Palette> {
+        let hex = match name {
+            "classic" => ["0000ff", "ffff19", "00ffff", "ff00ff"],
+            "mono" => ["000000", "ffffff", "aaaaaa", "555555"],
+            "amber" => ["1a0f00", "ffb000", "cc7a00", "663d00"],
+            "green" => ["001100", "33ff33", "22aa22", "115511"],
+            "lcd" => ["9bbc0f", "0f380f", "306230", "8bac0f"],
+            "octo" => ["996600", "ffcc00", "ff6600", "662200"],
+            _ => return None,
+        };
+
+        let mut colors = [Color::RGB(0, 0, 0); 4];
+        for i in 0..4 {
+            colors[i] = parse_color(hex[i]).unwrap();
+        }
+
+        Some(Palette { colors })
+    }
+
+    //comma separated list of 2 or 4 hex colours, e.g. "000000,ffffff"
+    pub fn from_hex_list(list: &str) -> Result<Palette, String> {
+        let parsed = list.split(',')
+            .map(|c| parse_color(c.trim()))
+            .collect::<Result<Vec<Color>, String>>()?;
+
+        match parsed.len() {
+            2 => Ok(Palette { colors: [parsed[0], parsed[1], parsed[1], parsed[1]] }),
+            4 => Ok(Palette { colors: [parsed[0], parsed[1], parsed[2], parsed[3]] }),
+            n => Err(format!("expected 2 or 4 colours, got {}", n)),
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::from_name("classic").unwrap()
+    }
+}
+
+pub fn parse_color(text: &str) -> Result<Color, String> {
+    let text = text.trim_start_matches('#');
+    let bytes = hex::decode(text)
+        .map_err(|e| format!("bad colour '{}': {}", text, e))?;
+
+    if bytes.len() != 3 {
+        return Err(format!("bad colour '{}': expected 6 hex digits", text));
+    }
+
+    Ok(Color::RGB(bytes[0], bytes[1], bytes[2]))
+}
+
+#[derive(Clone, Debug)]
+pub struct DisplayConfig {
+    pub scale: u32,
+    pub fullscreen: bool,
+    pub palette: Palette,
+    pub phosphor: PhosphorMode,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> DisplayConfig {
+        DisplayConfig {
+            scale: 10,
+            fullscreen: false,
+            palette: Palette::default(),
+            phosphor: PhosphorMode::default(),
+        }
+    }
+}
+
+impl DisplayConfig {
+    pub fn window_size(&self) -> (u32, u32) {
+        (DISPLAY_WIDTH * self.scale, DISPLAY_HEIGHT * self.scale)
+    }
+}
+
+pub fn blend(background: Color, foreground: Color, amount: f32) -> Color {
+    let mix = |bg: u8, fg: u8| (bg as f32 + (fg as f32 - bg as f32) * amount).round() as u8;
+
+    Color::RGB(mix(background.r, foreground.r),
+        mix(background.g, foreground.g),
+        mix(background.b, foreground.b))
+}
+
+//draws a whole frame at once, the canvas logical size is DISPLAY_WIDTH x DISPLAY_HEIGHT
+pub fn render_frame(canvas: &mut WindowCanvas, intensity: &Intensity, palette: &Palette) {
+    canvas.set_draw_color(palette.background());
+    canvas.clear();
+
+    for (x, column) in intensity.iter().enumerate() {
+        for (y, &amount) in column.iter().enumerate() {
+            if amount <= 0.0 {
+                continue;
+            }
+
+            canvas.set_draw_color(blend(palette.background(), palette.foreground(), amount));
+            canvas.fill_rect(Rect::new(x as i32, y as i32, 1, 1))
+                .expect("could not draw pixel");
+        }
+    }
+
+    canvas.present();
+}
@@ -0,0 +1,106 @@
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+
+use ratatui::{crossterm::event::KeyEvent, text::Line};
+
+use crate::config::Config;
+use crate::debugger::Debugger;
+use crate::governor::Governor;
+use crate::session::Session;
+
+//The SDL frontend's emulation thread. It owns the session, the debugger and
+//the governor, takes input as Commands and sends back what the window and the
+//debug view need as Events, so a slow draw or a window being dragged never
+//holds up the machine. The thread stops on Quit or when the window side hangs
+//up, and returns the governor's summary for --benchmark.
+
+pub enum Command {
+    //the keypad key held down, None when nothing is
+    Key(Option<u8>),
+    Rewind(bool),
+    FastForward(bool),
+    Screenshot,
+    ToggleRecording,
+    //keys pressed in the terminal the debug view is drawn in
+    DebuggerKey(KeyEvent),
+    Quit,
+}
+
+pub enum Event {
+    //the display after the last frame run, sent once per display frame
+    Frame(Box<[[u8; 64]; 128]>),
+    //whether the sound timer is running, sent when that changes
+    Sound(bool),
+    Status(String),
+    Debug(Vec<Line<'static>>),
+}
+
+pub fn run(chp8_code: &[u8], config: &Config, commands: Receiver<Command>, events: Sender<Event>)
+    -> Result<String, String> {
+
+    let mut session = Session::new(chp8_code, config)?;
+    let mut debugger = Debugger::new();
+    let mut governor = Governor::new(&config.speed);
+    let mut held_key: Option<u8> = None;
+    let mut rewinding = false;
+    let mut sound = false;
+
+    'emulation: loop {
+        loop {
+            let command = match commands.try_recv() {
+                Ok(command) => command,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => break 'emulation,
+            };
+
+            match command {
+                Command::Key(key) => held_key = key,
+                Command::Rewind(on) => rewinding = on,
+                Command::FastForward(on) => governor.fast_forward = on,
+                Command::Screenshot => session.capture.request_screenshot(),
+                Command::ToggleRecording => session.capture.toggle_recording(),
+                Command::DebuggerKey(key) => {
+                    debugger.handle_key(&key, &mut session)?;
+                },
+                Command::Quit => break 'emulation,
+            }
+        }
+
+        let status = if debugger.paused {
+            None
+        } else if rewinding {
+            session.rewind_frame()?
+        } else {
+            let mut status = None;
+            while governor.frame_due() {
+                status = session.run_frame(held_key.unwrap_or(0xff))?.or(status);
+            }
+            status
+        };
+
+        let machine = &session.machine;
+        let mut outgoing = vec![
+            Event::Frame(Box::new(machine.display_mem)),
+            Event::Debug(debugger.lines(&session)),
+        ];
+        if let Some(status) = status {
+            outgoing.push(Event::Status(status));
+        }
+        if (machine.reg.ST > 0) != sound {
+            sound = machine.reg.ST > 0;
+            outgoing.push(Event::Sound(sound));
+        }
+
+        for event in outgoing {
+            if events.send(event).is_err() {
+                break 'emulation;
+            }
+        }
+
+        governor.wait();
+    }
+
+    let summary = governor.summary(&session.machine);
+    session.finish()?;
+
+    Ok(summary)
+}
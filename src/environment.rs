@@ -0,0 +1,392 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::frame::FrameRunner;
+use crate::machine::{Machine, DISPLAY_WIDTH, DISPLAY_HEIGHT};
+use crate::quirks::Quirks;
+use crate::recompiler::Backend;
+
+//Gym style environment for training agents on a ROM: reset() starts an
+//episode, step() holds some keys for a few frames and says what the agent
+//earned and whether the episode is over. What counts as reward and as game
+//over is different for every ROM, so it comes from a spec file naming RAM
+//addresses:
+//
+//  rom = pong.ch8
+//  frame-skip = 4
+//  max-frames = 18000
+//  # score, three digits as Fx33 stores them
+//  reward = bcd 2f4
+//  # lives, losing one costs 10
+//  reward = byte 2f0 -10
+//  done = byte 2f0 eq 0
+//
+//Each reward line pays its scale (1 when left out) times how much the value
+//changed since the last step, done lines end the episode as soon as one
+//holds. Addresses are hex, values and scales decimal. The ROM path is
+//relative to the spec file, and quirks, ipf and backend work as on the
+//command line.
+
+const USAGE: &str = "usage: chip8 env [--episodes N] [--seed N] <spec>
+
+Runs a random agent through the environment the spec describes and prints
+the return of every episode, to try out a spec file.";
+
+//bytes in an observation, one per pixel, row by row
+pub const OBSERVATION_SIZE: usize = (DISPLAY_WIDTH * DISPLAY_HEIGHT) as usize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ValueKind {
+    Byte,
+    //big endian
+    Word,
+    //hundreds, tens and ones in three bytes, the way Fx33 writes them
+    Bcd,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct RamValue {
+    kind: ValueKind,
+    addr: u16,
+}
+
+impl RamValue {
+    fn parse(kind: &str, addr: &str) -> Result<RamValue, String> {
+        let kind = match kind {
+            "byte" => ValueKind::Byte,
+            "word" => ValueKind::Word,
+            "bcd" => ValueKind::Bcd,
+            _ => return Err(format!("unknown value kind '{}', expected byte, word or bcd", kind)),
+        };
+        let addr = u16::from_str_radix(addr.trim_start_matches("0x"), 16)
+            .ok().filter(|addr| *addr < 4096)
+            .ok_or(format!("bad address '{}'", addr))?;
+
+        Ok(RamValue { kind, addr })
+    }
+
+    fn read(&self, machine: &Machine) -> i64 {
+        let byte = |offset: u16| machine.memory[(self.addr + offset) as usize % 4096] as i64;
+        match self.kind {
+            ValueKind::Byte => byte(0),
+            ValueKind::Word => (byte(0) << 8) | byte(1),
+            ValueKind::Bcd => byte(0) * 100 + byte(1) * 10 + byte(2),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Test {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct DoneCondition {
+    value: RamValue,
+    test: Test,
+    operand: i64,
+}
+
+impl DoneCondition {
+    fn holds(&self, machine: &Machine) -> bool {
+        let value = self.value.read(machine);
+        match self.test {
+            Test::Equal => value == self.operand,
+            Test::NotEqual => value != self.operand,
+            Test::Less => value < self.operand,
+            Test::LessOrEqual => value <= self.operand,
+            Test::Greater => value > self.operand,
+            Test::GreaterOrEqual => value >= self.operand,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EnvSpec {
+    pub rom_path: String,
+    pub quirks: Quirks,
+    pub backend: Backend,
+    //frames each action is held for
+    pub frame_skip: u32,
+    //episodes are cut off after this many frames
+    pub max_frames: Option<u64>,
+    rewards: Vec<(RamValue, f32)>,
+    done: Vec<DoneCondition>,
+}
+
+impl EnvSpec {
+    pub fn load(path: &str) -> Result<EnvSpec, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path, e))?;
+
+        let mut spec = EnvSpec {
+            rom_path: String::new(),
+            quirks: Quirks::default(),
+            backend: Backend::default(),
+            frame_skip: 1,
+            max_frames: None,
+            rewards: Vec::new(),
+            done: Vec::new(),
+        };
+
+        //the quirks profile goes first so an ipf line anywhere still applies
+        for profiles in [true, false] {
+            for (num, line) in contents.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                let (key, value) = line.split_once('=')
+                    .ok_or(format!("{}:{}: expected key = value", path, num + 1))?;
+                let key = key.trim();
+                if (key == "quirks") != profiles {
+                    continue;
+                }
+                spec.apply(key, value.trim())
+                    .map_err(|e| format!("{}:{}: {}", path, num + 1, e))?;
+            }
+        }
+
+        if spec.rom_path.is_empty() {
+            return Err(format!("{}: no rom given", path));
+        }
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        spec.rom_path = dir.join(&spec.rom_path).to_string_lossy().into_owned();
+
+        Ok(spec)
+    }
+
+    fn apply(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let words: Vec<&str> = value.split_whitespace().collect();
+
+        match key {
+            "rom" => self.rom_path = value.to_string(),
+            "quirks" => {
+                self.quirks = Quirks::from_profile(value)
+                    .ok_or(format!("unknown profile '{}'", value))?;
+            },
+            "ipf" => {
+                self.quirks.instructions_per_frame = value.parse()
+                    .ok().filter(|ipf| *ipf > 0)
+                    .ok_or(format!("bad instructions per frame '{}'", value))?;
+            },
+            "backend" => self.backend = Backend::parse(value)?,
+            "frame-skip" => {
+                self.frame_skip = value.parse()
+                    .ok().filter(|skip| *skip > 0)
+                    .ok_or(format!("bad frame skip '{}'", value))?;
+            },
+            "max-frames" => {
+                let frames = value.parse()
+                    .map_err(|_| format!("bad frame count '{}'", value))?;
+                self.max_frames = Some(frames);
+            },
+            "reward" => {
+                let scale = match words.len() {
+                    2 => 1.0,
+                    3 => words[2].parse().map_err(|_| format!("bad reward scale '{}'", words[2]))?,
+                    _ => return Err("expected reward = KIND ADDR [SCALE]".to_string()),
+                };
+                self.rewards.push((RamValue::parse(words[0], words[1])?, scale));
+            },
+            "done" => {
+                if words.len() != 4 {
+                    return Err("expected done = KIND ADDR eq|ne|lt|le|gt|ge VALUE".to_string());
+                }
+                let test = match words[2] {
+                    "eq" | "==" => Test::Equal,
+                    "ne" | "!=" => Test::NotEqual,
+                    "lt" | "<" => Test::Less,
+                    "le" | "<=" => Test::LessOrEqual,
+                    "gt" | ">" => Test::Greater,
+                    "ge" | ">=" => Test::GreaterOrEqual,
+                    _ => return Err(format!("unknown test '{}'", words[2])),
+                };
+                let operand = words[3].parse()
+                    .map_err(|_| format!("bad value '{}'", words[3]))?;
+                self.done.push(DoneCondition { value: RamValue::parse(words[0], words[1])?, test, operand });
+            },
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+
+        Ok(())
+    }
+}
+
+pub struct StepResult {
+    pub observation: Vec<u8>,
+    pub reward: f32,
+    pub done: bool,
+}
+
+pub struct Environment {
+    spec: EnvSpec,
+    //shared with the other instances of a batch
+    chp8_code: Arc<[u8]>,
+    pub machine: Machine,
+    runner: FrameRunner,
+    seed: u64,
+    episodes: u64,
+    //frames into the current episode
+    frames: u64,
+    //what each reward value read at the end of the last step
+    reward_values: Vec<i64>,
+    done: bool,
+}
+
+impl Environment {
+    //episode n runs with seed + n so they don't all play out the same
+    pub fn new(spec: EnvSpec, seed: u64) -> Result<Environment, String> {
+        let chp8_code = fs::read(&spec.rom_path)
+            .map_err(|e| format!("could not read {}: {}", spec.rom_path, e))?;
+        Environment::with_code(spec, chp8_code.into(), seed)
+    }
+
+    pub fn with_code(spec: EnvSpec, chp8_code: Arc<[u8]>, seed: u64) -> Result<Environment, String> {
+        let machine = Machine::new(&chp8_code, seed, &spec.quirks)?;
+        let runner = FrameRunner::new(&spec.quirks, spec.backend)?;
+
+        Ok(Environment {
+            spec,
+            chp8_code,
+            machine,
+            runner,
+            seed,
+            episodes: 0,
+            frames: 0,
+            reward_values: Vec::new(),
+            done: true,
+        })
+    }
+
+    pub fn reset(&mut self) -> Result<Vec<u8>, String> {
+        let seed = self.seed.wrapping_add(self.episodes);
+        self.machine = Machine::new(&self.chp8_code, seed, &self.spec.quirks)?;
+        //compiled blocks belong to the machine they were made for
+        self.runner = FrameRunner::new(&self.spec.quirks, self.spec.backend)?;
+        self.episodes += 1;
+        self.frames = 0;
+        self.reward_values = self.spec.rewards.iter().map(|(value, _)| value.read(&self.machine)).collect();
+        self.done = false;
+
+        Ok(self.observation())
+    }
+
+    //holds action_keys, bit n for key n, for frame-skip frames or until the
+    //episode ends
+    pub fn step(&mut self, action_keys: u16) -> Result<StepResult, String> {
+        if self.done {
+            return Err("the episode is over, reset the environment first".to_string());
+        }
+
+        for _ in 0..self.spec.frame_skip {
+            self.runner.run_frame(&mut self.machine, action_keys)?;
+            self.frames += 1;
+
+            let game_over = self.spec.done.iter().any(|condition| condition.holds(&self.machine));
+            let out_of_time = self.spec.max_frames.is_some_and(|limit| self.frames >= limit);
+            if game_over || out_of_time {
+                self.done = true;
+                break;
+            }
+        }
+
+        let mut reward = 0.0;
+        for ((value, scale), last) in self.spec.rewards.iter().zip(self.reward_values.iter_mut()) {
+            let now = value.read(&self.machine);
+            reward += scale * (now - *last) as f32;
+            *last = now;
+        }
+
+        Ok(StepResult {
+            observation: self.observation(),
+            reward,
+            done: self.done,
+        })
+    }
+
+    //the display, one byte of 0 or 1 per pixel, row by row
+    pub fn observation(&self) -> Vec<u8> {
+        observation(&self.machine)
+    }
+}
+
+pub fn observation(machine: &Machine) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(OBSERVATION_SIZE);
+    for y in 0..DISPLAY_HEIGHT as usize {
+        for x in 0..DISPLAY_WIDTH as usize {
+            pixels.push(machine.display_mem[x][y]);
+        }
+    }
+    pixels
+}
+
+//args are everything after "env"
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut episodes = 10;
+    let mut seed = 0;
+    let mut spec_path = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--episodes" => {
+                let value = iter.next().ok_or(USAGE.to_string())?;
+                episodes = value.parse().map_err(|_| format!("bad episode count '{}'", value))?;
+            },
+            "--seed" => {
+                let value = iter.next().ok_or(USAGE.to_string())?;
+                seed = value.parse().map_err(|_| format!("bad seed '{}'", value))?;
+            },
+            _ if spec_path.is_none() && !arg.starts_with("--") => spec_path = Some(arg.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let spec = EnvSpec::load(&spec_path.ok_or(USAGE.to_string())?)?;
+    if spec.max_frames.is_none() && spec.done.is_empty() {
+        return Err("the spec has no done conditions or max-frames, episodes would never end".to_string());
+    }
+
+    let mut env = Environment::new(spec, seed)?;
+    let mut agent = ChaCha8Rng::seed_from_u64(seed);
+    let mut total = 0.0;
+
+    for episode in 0..episodes {
+        env.reset()?;
+        let mut episode_return = 0.0;
+        let mut steps = 0;
+
+        let last = loop {
+            //one of the sixteen keys or none
+            let keys = match agent.gen_range(0..17) {
+                16 => 0,
+                key => 1 << key,
+            };
+            let result = env.step(keys)?;
+            episode_return += result.reward;
+            steps += 1;
+            if result.done {
+                break result;
+            }
+        };
+
+        let lit = last.observation.iter().filter(|pixel| **pixel != 0).count();
+        println!("episode {}: return {} after {} steps, {} frames, {} pixels lit at the end",
+            episode, episode_return, steps, env.frames, lit);
+        total += episode_return;
+    }
+
+    println!("mean return {:.3} over {} episodes", total / episodes.max(1) as f32, episodes);
+    Ok(())
+}
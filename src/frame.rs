@@ -0,0 +1,104 @@
+use crate::machine::{Machine, Registers};
+use crate::quirks::Quirks;
+use crate::recompiler::{Backend, Recompiler};
+use crate::timing::{self, Timing, VIP_FRAME_CYCLES};
+
+//Where frames start and end. The session uses the two functions around its
+//own instruction loop, FrameRunner uses them to run a bare Machine for
+//callers that want neither a frontend nor a session, like the RL environment
+//and the C API.
+
+//whether the frame the machine is in has run its course
+pub fn frame_done(machine: &Machine, timing: Timing, instructions_per_frame: usize) -> bool {
+    match timing {
+        Timing::Fixed => machine.cycle - machine.frame_start_cycle >= instructions_per_frame as u64,
+        Timing::Vip => machine.machine_cycles >= (machine.frame + 1) * VIP_FRAME_CYCLES,
+    }
+}
+
+//bookkeeping once the interpreter has run the instruction at pc, before holds
+//the registers from ahead of it. True when it was a Dxyn waiting for vblank,
+//which ends the frame early rather than spinning on it for the rest of the frame
+pub fn after_step(machine: &mut Machine, timing: Timing, instruction: u16, pc: u16, before: &Registers) -> bool {
+    if timing == Timing::Vip {
+        machine.machine_cycles += timing::vip_cycles(instruction, before, &machine.reg);
+    }
+
+    let waiting_for_vblank = instruction >> 12 == 0xd && machine.reg.PC == pc;
+    if waiting_for_vblank && timing == Timing::Vip {
+        let frame_end = (machine.frame + 1) * VIP_FRAME_CYCLES;
+        machine.machine_cycles = machine.machine_cycles.max(frame_end);
+    }
+
+    waiting_for_vblank
+}
+
+pub struct FrameRunner {
+    timing: Timing,
+    instructions_per_frame: usize,
+    recompiler: Option<Recompiler>,
+}
+
+impl FrameRunner {
+    pub fn new(quirks: &Quirks, backend: Backend) -> Result<FrameRunner, String> {
+        let recompiler = match backend {
+            Backend::Interpreter => None,
+            Backend::Recompiler if quirks.timing == Timing::Vip => {
+                return Err("the recompiler only supports fixed timing".to_string());
+            },
+            Backend::Recompiler => Some(Recompiler::new()),
+        };
+
+        Ok(FrameRunner {
+            timing: quirks.timing,
+            instructions_per_frame: quirks.instructions_per_frame,
+            recompiler,
+        })
+    }
+
+    //runs to the end of the frame with keys held, bit n for key n
+    pub fn run_frame(&mut self, machine: &mut Machine, keys: u16) -> Result<(), String> {
+        machine.keys = keys;
+        let (timing, instructions_per_frame) = (self.timing, self.instructions_per_frame);
+
+        match &mut self.recompiler {
+            Some(recompiler) => {
+                let run = machine.cycle - machine.frame_start_cycle;
+                recompiler.run(machine, (instructions_per_frame as u64).saturating_sub(run))?;
+            },
+            None => while !step_instruction(machine, timing, instructions_per_frame)? {},
+        }
+
+        end_frame(machine, timing, instructions_per_frame);
+        Ok(())
+    }
+
+    //runs a single instruction with whatever key is held, ending the frame
+    //if that was its last one
+    pub fn step(&mut self, machine: &mut Machine) -> Result<(), String> {
+        let (timing, instructions_per_frame) = (self.timing, self.instructions_per_frame);
+
+        if step_instruction(machine, timing, instructions_per_frame)? {
+            end_frame(machine, timing, instructions_per_frame);
+        }
+        Ok(())
+    }
+}
+
+//true when the frame is over after it
+fn step_instruction(machine: &mut Machine, timing: Timing, instructions_per_frame: usize) -> Result<bool, String> {
+    let pc = machine.reg.PC;
+    let instruction = machine.fetch(pc);
+    let before = machine.reg.clone();
+    machine.step()?;
+
+    Ok(after_step(machine, timing, instruction, pc, &before) || frame_done(machine, timing, instructions_per_frame))
+}
+
+fn end_frame(machine: &mut Machine, timing: Timing, instructions_per_frame: usize) {
+    //an instruction as slow as 00E0 can outlast a whole VIP frame
+    machine.tick_timers();
+    while frame_done(machine, timing, instructions_per_frame) {
+        machine.tick_timers();
+    }
+}
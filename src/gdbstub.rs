@@ -0,0 +1,577 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+
+use crate::config::Config;
+use crate::session::Session;
+
+//GDB remote serial protocol server so any RSP client can drive the machine.
+//Registers are numbered V0-VF (0-15), I, PC, SP, DT and ST (16-20), 16 bit
+//registers are sent big endian like everything else on a CHIP-8. Breakpoints
+//are kept here instead of being patched into memory since there is no trap
+//opcode, and the machine runs as fast as it can while continuing.
+
+const REGISTER_COUNT: usize = 21;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+//signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+trait Connection: Read + Write {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Connection for std::net::TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        std::net::TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for std::os::unix::net::UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        std::os::unix::net::UnixStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+//"1234" and "host:port" listen on TCP, "unix:PATH" on a Unix socket
+fn accept(address: &str) -> Result<Box<dyn Connection>, String> {
+    #[cfg(unix)]
+    if let Some(path) = address.strip_prefix("unix:") {
+        //a socket left behind by an earlier run would make bind fail, but
+        //anything else at that path isn't ours to delete
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+                .map_err(|e| format!("could not remove the old socket {}: {}", path, e))?,
+            Ok(_) => return Err(format!("{} already exists and is not a socket", path)),
+            Err(_) => {},
+        }
+        let listener = UnixListener::bind(path)
+            .map_err(|e| format!("could not listen on {}: {}", path, e))?;
+        println!("waiting for gdb on {}", path);
+        let (stream, _) = listener.accept().map_err(|e| e.to_string())?;
+        return Ok(Box::new(stream));
+    }
+
+    let address = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("127.0.0.1:{}", address)
+    };
+
+    let listener = TcpListener::bind(&address)
+        .map_err(|e| format!("could not listen on {}: {}", address, e))?;
+    println!("waiting for gdb on {}", address);
+    let (stream, peer) = listener.accept().map_err(|e| e.to_string())?;
+    stream.set_nodelay(true).map_err(|e| e.to_string())?;
+    println!("gdb connected from {}", peer);
+
+    Ok(Box::new(stream))
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    hex::decode(text).map_err(|_| format!("bad hex data '{}'", text))
+}
+
+fn parse_number(text: &str) -> Result<usize, String> {
+    usize::from_str_radix(text, 16).map_err(|_| format!("bad number '{}'", text))
+}
+
+//"addr,length" as used by m, M and Z
+fn parse_range(text: &str) -> Result<(usize, usize), String> {
+    let (addr, length) = text.split_once(',').ok_or(format!("bad range '{}'", text))?;
+    Ok((parse_number(addr)?, parse_number(length)?))
+}
+
+struct GdbStub<'a> {
+    conn: Box<dyn Connection>,
+    session: Session<'a>,
+    breakpoints: HashSet<u16>,
+    no_ack: bool,
+    //held key set with "monitor key", 0xff for none
+    key: u8,
+}
+
+impl<'a> GdbStub<'a> {
+    fn read_byte(&mut self) -> Result<Option<u8>, String> {
+        let mut byte = [0];
+        match self.conn.read(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    //the next packet's contents, None once the client disconnects
+    fn read_packet(&mut self) -> Result<Option<String>, String> {
+        loop {
+            //acks and stray interrupts between packets carry nothing
+            match self.read_byte()? {
+                Some(b'$') => {},
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+
+            let mut sum = [0; 2];
+            for digit in sum.iter_mut() {
+                *digit = self.read_byte()?.ok_or("connection closed")?;
+            }
+            let expected = u8::from_str_radix(&String::from_utf8_lossy(&sum), 16).ok();
+
+            if !self.no_ack {
+                let ack: &[u8] = if expected == Some(checksum(&data)) { b"+" } else { b"-" };
+                self.conn.write_all(ack).map_err(|e| e.to_string())?;
+                if ack == b"-" {
+                    continue;
+                }
+            }
+
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn send(&mut self, reply: &str) -> Result<(), String> {
+        let mut data = Vec::with_capacity(reply.len());
+        for byte in reply.bytes() {
+            if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+                data.push(b'}');
+                data.push(byte ^ 0x20);
+            } else {
+                data.push(byte);
+            }
+        }
+
+        let packet = format!("${}#{:02x}", String::from_utf8_lossy(&data), checksum(&data));
+        self.conn.write_all(packet.as_bytes()).map_err(|e| e.to_string())?;
+        self.conn.flush().map_err(|e| e.to_string())
+    }
+
+    fn registers(&self) -> Vec<u8> {
+        let reg = &self.session.machine.reg;
+        let mut data = reg.V.to_vec();
+        data.extend_from_slice(&reg.I.to_be_bytes());
+        data.extend_from_slice(&reg.PC.to_be_bytes());
+        data.extend_from_slice(&reg.SP.to_be_bytes());
+        data.push(reg.DT);
+        data.push(reg.ST);
+        data
+    }
+
+    fn register(&self, number: usize) -> Option<Vec<u8>> {
+        let reg = &self.session.machine.reg;
+        match number {
+            0..=15 => Some(vec![reg.V[number]]),
+            16 => Some(reg.I.to_be_bytes().to_vec()),
+            17 => Some(reg.PC.to_be_bytes().to_vec()),
+            18 => Some(reg.SP.to_be_bytes().to_vec()),
+            19 => Some(vec![reg.DT]),
+            20 => Some(vec![reg.ST]),
+            _ => None,
+        }
+    }
+
+    //takes as many bytes from value as the register is wide, returns how many
+    fn set_register(&mut self, number: usize, value: &[u8]) -> Option<usize> {
+        let reg = &mut self.session.machine.reg;
+        let word = || Some(u16::from_be_bytes([*value.first()?, *value.get(1)?]));
+
+        match number {
+            0..=15 => reg.V[number] = *value.first()?,
+            16 => reg.I = word()?,
+            17 => reg.PC = word()? & 0xfff,
+            18 => reg.SP = word()?,
+            19 => reg.DT = *value.first()?,
+            20 => reg.ST = *value.first()?,
+            _ => return None,
+        }
+
+        Some(if (16..=18).contains(&number) { 2 } else { 1 })
+    }
+
+    //runs one instruction and reports why it stopped, if it did
+    fn step(&mut self) -> Option<u8> {
+        match self.session.step_instruction(self.key) {
+            Ok(Some(status)) => {
+                println!("{}", status);
+                None
+            },
+            Ok(None) => None,
+            Err(e) => {
+                eprintln!("{}", e);
+                Some(SIGSEGV)
+            },
+        }
+    }
+
+    fn resume(&mut self) -> Result<u8, String> {
+        //a breakpoint at the current PC was just reported, get past it first
+        if let Some(signal) = self.step() {
+            return Ok(signal);
+        }
+
+        self.conn.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        let signal = loop {
+            if self.breakpoints.contains(&self.session.machine.reg.PC) {
+                break SIGTRAP;
+            }
+            if let Some(signal) = self.step() {
+                break signal;
+            }
+
+            //check for ctrl-c once a frame rather than every instruction
+            let machine = &self.session.machine;
+            if machine.cycle == machine.frame_start_cycle {
+                let mut byte = [0];
+                match self.conn.read(&mut byte) {
+                    Ok(0) => break SIGINT,
+                    Ok(_) if byte[0] == 0x03 => break SIGINT,
+                    Ok(_) => {},
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {},
+                    Err(e) => return Err(e.to_string()),
+                }
+            }
+        };
+
+        self.conn.set_nonblocking(false).map_err(|e| e.to_string())?;
+
+        Ok(signal)
+    }
+
+    fn monitor(&mut self, command: &str) -> Result<String, String> {
+        let command = String::from_utf8(from_hex(command)?)
+            .map_err(|_| "monitor command is not text".to_string())?;
+        let mut words = command.split_whitespace();
+
+        let output = match (words.next(), words.next()) {
+            (Some("key"), Some("none")) => {
+                self.key = 0xff;
+                "key released\n".to_string()
+            },
+            (Some("key"), Some(key)) => {
+                let key = u8::from_str_radix(key, 16)
+                    .ok()
+                    .filter(|key| *key < 16)
+                    .ok_or(format!("bad key '{}'", key))?;
+                self.key = key;
+                format!("holding key {:x}\n", key)
+            },
+            _ => "commands: key 0-f, key none\n".to_string(),
+        };
+
+        Ok(to_hex(output.as_bytes()))
+    }
+
+    fn read_features(&self, args: &str) -> Result<String, String> {
+        let (annex, range) = args.split_once(':').ok_or("bad qXfer request")?;
+        if annex != "target.xml" {
+            return Ok("E00".to_string());
+        }
+
+        let (offset, length) = parse_range(range)?;
+        let data = TARGET_XML.as_bytes();
+        let start = offset.min(data.len());
+        let end = start.saturating_add(length).min(data.len());
+        let marker = if end == data.len() { "l" } else { "m" };
+
+        Ok(format!("{}{}", marker, String::from_utf8_lossy(&data[start..end])))
+    }
+
+    //register, memory and breakpoint packets, Err when they don't parse
+    fn access(&mut self, command: &str, args: &str) -> Result<String, String> {
+        let reply = match command {
+            "G" => {
+                //all or nothing, a short block would leave half the registers changed
+                let data = from_hex(args)?;
+                let expected = self.registers().len();
+                if data.len() != expected {
+                    return Err(format!("G packet has {} bytes of registers, expected {}", data.len(), expected));
+                }
+
+                let mut pos = 0;
+                for number in 0..REGISTER_COUNT {
+                    match self.set_register(number, &data[pos..]) {
+                        Some(width) => pos += width,
+                        None => break,
+                    }
+                }
+                "OK".to_string()
+            },
+            "p" => match self.register(parse_number(args)?) {
+                Some(value) => to_hex(&value),
+                None => "E01".to_string(),
+            },
+            "P" => {
+                let (number, value) = args.split_once('=').ok_or("bad P packet")?;
+                match self.set_register(parse_number(number)?, &from_hex(value)?) {
+                    Some(_) => "OK".to_string(),
+                    None => "E01".to_string(),
+                }
+            },
+            "m" => {
+                let (addr, length) = parse_range(args)?;
+                let memory = &self.session.machine.memory;
+                match addr.checked_add(length) {
+                    Some(end) if addr < memory.len() => to_hex(&memory[addr..end.min(memory.len())]),
+                    _ => "E14".to_string(),
+                }
+            },
+            "M" => {
+                let (range, data) = args.split_once(':').ok_or("bad M packet")?;
+                let (addr, length) = parse_range(range)?;
+                let data = from_hex(data)?;
+                match addr.checked_add(length) {
+                    Some(end) if end <= self.session.machine.memory.len() && data.len() == length => {
+                        for (offset, byte) in data.into_iter().enumerate() {
+                            self.session.machine.poke((addr + offset) as u16, byte);
+                        }
+                        "OK".to_string()
+                    },
+                    _ => "E14".to_string(),
+                }
+            },
+            //software and hardware breakpoints are the same thing here
+            _ => {
+                let (kind, addr) = args.split_once(',').ok_or(format!("bad {} packet", command))?;
+                let addr = parse_number(addr.split(',').next().unwrap_or(""))?;
+                let addr = u16::try_from(addr).map_err(|_| format!("bad address '{:x}'", addr))?;
+
+                match kind {
+                    "0" | "1" => {
+                        if command == "Z" {
+                            self.breakpoints.insert(addr);
+                        } else {
+                            self.breakpoints.remove(&addr);
+                        }
+                        "OK".to_string()
+                    },
+                    _ => String::new(),
+                }
+            },
+        };
+
+        Ok(reply)
+    }
+
+    //the reply to one packet, None for packets that end the session
+    fn handle(&mut self, packet: &str) -> Result<Option<String>, String> {
+        let (command, args) = packet.split_at(packet.len().min(1));
+
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => to_hex(&self.registers()),
+            //a malformed packet is the client's mistake, tell it and keep serving
+            "G" | "p" | "P" | "m" | "M" | "Z" | "z" => self.access(command, args).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                "E01".to_string()
+            }),
+            "c" | "s" if !args.is_empty() && parse_number(args).is_err() => "E01".to_string(),
+            "c" => {
+                if !args.is_empty() {
+                    self.session.machine.reg.PC = parse_number(args)? as u16 & 0xfff;
+                }
+                format!("S{:02x}", self.resume()?)
+            },
+            "s" => {
+                if !args.is_empty() {
+                    self.session.machine.reg.PC = parse_number(args)? as u16 & 0xfff;
+                }
+                format!("S{:02x}", self.step().unwrap_or(SIGTRAP))
+            },
+            "H" | "T" => "OK".to_string(),
+            "D" => {
+                self.send("OK")?;
+                return Ok(None);
+            },
+            "k" => return Ok(None),
+            "q" | "Q" => {
+                if args.starts_with("Supported") {
+                    "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+".to_string()
+                } else if args == "StartNoAckMode" {
+                    //this packet was still acked, the client stops after our reply
+                    self.no_ack = true;
+                    "OK".to_string()
+                } else if args == "Attached" {
+                    "1".to_string()
+                } else if args == "C" {
+                    "QC1".to_string()
+                } else if args == "fThreadInfo" {
+                    "m1".to_string()
+                } else if args == "sThreadInfo" {
+                    "l".to_string()
+                } else if let Some(request) = args.strip_prefix("Xfer:features:read:") {
+                    self.read_features(request).unwrap_or_else(|_| "E01".to_string())
+                } else if let Some(command) = args.strip_prefix("Rcmd,") {
+                    match self.monitor(command) {
+                        Ok(output) => output,
+                        Err(e) => to_hex(format!("{}\n", e).as_bytes()),
+                    }
+                } else {
+                    String::new()
+                }
+            },
+            //anything else is unsupported, which an empty reply says
+            _ => String::new(),
+        };
+
+        Ok(Some(reply))
+    }
+}
+
+pub fn serve(chp8_code: &[u8], config: &Config, address: &str) -> Result<(), String> {
+    let session = Session::new(chp8_code, config)?;
+    let conn = accept(address)?;
+
+    let mut stub = GdbStub {
+        conn,
+        session,
+        breakpoints: HashSet::new(),
+        no_ack: false,
+        key: 0xff,
+    };
+
+    while let Some(packet) = stub.read_packet()? {
+        match stub.handle(&packet)? {
+            Some(reply) => stub.send(&reply)?,
+            None => break,
+        }
+    }
+
+    println!("gdb disconnected");
+    stub.session.finish()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+
+    //a client that doesn't ack, the stub doesn't wait for acks anyway
+    fn request(stream: &mut UnixStream, packet: &str) -> String {
+        write!(stream, "${}#{:02x}", packet, checksum(packet.as_bytes())).unwrap();
+
+        let mut reply = Vec::new();
+        let mut byte = [0];
+        while !reply.ends_with(b"#") {
+            stream.read_exact(&mut byte).unwrap();
+            reply.push(byte[0]);
+        }
+        let mut sum = [0; 2];
+        stream.read_exact(&mut sum).unwrap();
+
+        let reply = String::from_utf8(reply).unwrap();
+        reply.trim_start_matches('+').trim_start_matches('$').trim_end_matches('#').to_string()
+    }
+
+    #[test]
+    fn serves_a_unix_socket() {
+        let path = std::env::temp_dir().join(format!("chip8-gdb-test-{}", std::process::id()));
+        let address = format!("unix:{}", path.display());
+        let config = Config {
+            seed: Some(0),
+            history: 0,
+            cheat_dir: None,
+            ..Config::default()
+        };
+        //6001 7001 1202, V0 counts up forever
+        let chp8_code = [0x60, 0x01, 0x70, 0x01, 0x12, 0x02];
+
+        thread::scope(|scope| {
+            let server = scope.spawn(|| serve(&chp8_code, &config, &address));
+
+            let mut stream = loop {
+                match UnixStream::connect(&path) {
+                    Ok(stream) => break stream,
+                    Err(_) => thread::sleep(Duration::from_millis(10)),
+                }
+            };
+
+            assert_eq!(request(&mut stream, "?"), "S05");
+            let registers = request(&mut stream, "g");
+            assert_eq!(&registers[..2], "00");
+            assert_eq!(request(&mut stream, &format!("G{}", &registers[2..])), "E01");
+            assert_eq!(request(&mut stream, "g"), registers);
+            assert_eq!(request(&mut stream, &format!("G{}", registers)), "OK");
+            assert_eq!(request(&mut stream, "m200,6"), "600170011202");
+            assert_eq!(request(&mut stream, "m1000,1"), "E14");
+            assert_eq!(request(&mut stream, "Z0,204,2"), "OK");
+            assert_eq!(request(&mut stream, "c"), "S05");
+            //stopped at the breakpoint after 6001 and 7001 ran
+            assert_eq!(request(&mut stream, "p11"), "0204");
+            assert_eq!(request(&mut stream, "p0"), "02");
+            assert_eq!(request(&mut stream, "D"), "OK");
+
+            server.join().unwrap().unwrap();
+        });
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn leaves_other_files_alone() {
+        let path = std::env::temp_dir().join(format!("chip8-gdb-file-{}", std::process::id()));
+        std::fs::write(&path, "not a socket").unwrap();
+
+        let error = accept(&format!("unix:{}", path.display())).err().unwrap();
+        assert!(error.contains("not a socket"), "{}", error);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::machine::Machine;
+
+//Paces the windowed frontends at 60 emulated frames per second of real time.
+//Deadlines are kept on a fixed grid from the start rather than measured from
+//the end of the last frame, so time lost to one slow frame is made up by the
+//next instead of the game running slow.
+
+pub const FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+//sleep overshoots by up to a millisecond or so, the rest of the wait is spun
+const SPIN_TIME: Duration = Duration::from_micros(1_500);
+
+//further behind than this and the governor gives up catching up, after a
+//breakpoint or the window being dragged for example
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug)]
+pub struct SpeedConfig {
+    //frames run for each one shown while fast forward is held
+    pub fast_forward: u32,
+    //run as fast as possible and report the speed reached at the end
+    pub benchmark: bool,
+}
+
+impl Default for SpeedConfig {
+    fn default() -> SpeedConfig {
+        SpeedConfig {
+            fast_forward: 4,
+            benchmark: false,
+        }
+    }
+}
+
+pub struct Governor {
+    config: SpeedConfig,
+    pub fast_forward: bool,
+    started: Instant,
+    tick_start: Instant,
+    next_tick: Instant,
+    //frames run since the last one was shown
+    frames_this_tick: u32,
+}
+
+impl Governor {
+    pub fn new(config: &SpeedConfig) -> Governor {
+        let now = Instant::now();
+
+        Governor {
+            config: config.clone(),
+            fast_forward: false,
+            started: now,
+            tick_start: now,
+            next_tick: now + FRAME_TIME,
+            frames_this_tick: 0,
+        }
+    }
+
+    //true while the frontend should run another frame before drawing, one
+    //normally, the fast forward multiplier while it is held and as many as
+    //fit in a display frame when benchmarking
+    pub fn frame_due(&mut self) -> bool {
+        let due = if self.config.benchmark {
+            self.frames_this_tick == 0 || self.tick_start.elapsed() < FRAME_TIME
+        } else if self.fast_forward {
+            self.frames_this_tick < self.config.fast_forward
+        } else {
+            self.frames_this_tick < 1
+        };
+
+        if due {
+            self.frames_this_tick += 1;
+        }
+        due
+    }
+
+    //blocks until the next frame should be shown
+    pub fn wait(&mut self) {
+        if !self.config.benchmark {
+            let now = Instant::now();
+            if self.next_tick + MAX_LAG < now {
+                self.next_tick = now;
+            }
+
+            if let Some(remaining) = self.next_tick.checked_duration_since(now) {
+                if remaining > SPIN_TIME {
+                    thread::sleep(remaining - SPIN_TIME);
+                }
+            }
+            while Instant::now() < self.next_tick {
+                thread::yield_now();
+            }
+
+            self.next_tick += FRAME_TIME;
+        }
+
+        self.tick_start = Instant::now();
+        self.frames_this_tick = 0;
+    }
+
+    //speed reached since the governor was made, for --benchmark
+    pub fn summary(&self, machine: &Machine) -> String {
+        let seconds = self.started.elapsed().as_secs_f64().max(1e-9);
+        let fps = machine.frame as f64 / seconds;
+
+        format!("{} frames, {} instructions in {:.2}s: {:.0} frames/s ({:.1}x real time), {:.0} instructions/s",
+            machine.frame, machine.cycle, seconds, fps, fps / 60.0, machine.cycle as f64 / seconds)
+    }
+}
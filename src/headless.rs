@@ -0,0 +1,27 @@
+use crate::config::Config;
+use crate::governor::Governor;
+use crate::session::Session;
+
+//runs without a window or terminal and as fast as possible, frames are only
+//counted so screenshots, recordings and replays line up with a windowed run
+pub fn chp8_execute_headless(chp8_code: &[u8], config: &Config) -> Result<(), String> {
+    let mut session = Session::new(chp8_code, config)?;
+    //only used for its benchmark summary, headless never waits
+    let governor = Governor::new(&config.speed);
+
+    while config.frames.is_none_or(|limit| session.machine.frame < limit) {
+        if let Some(status) = session.run_frame(0xff)? {
+            println!("{}", status);
+        }
+
+        if session.replay_finished() && config.frames.is_none() {
+            break;
+        }
+    }
+
+    if config.speed.benchmark {
+        println!("{}", governor.summary(&session.machine));
+    }
+
+    session.finish()
+}
@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+
+use crate::machine::{Machine, Registers};
+
+//What an instruction or timer tick overwrote, so it can be undone. Which
+//memory and pixels an instruction can touch is known from its opcode, so only
+//those are saved instead of diffing the whole machine every step.
+struct Delta {
+    reg: Registers,
+    frame: u64,
+    cycle: u64,
+    frame_start_cycle: u64,
+    machine_cycles: u64,
+    rng_word_pos: u128,
+    memory: Vec<(u16, u8)>,
+    //x, y and previous value
+    pixels: Vec<(u8, u8, u8)>,
+    //timer ticks and pokes are undone together with the instruction before them
+    tick: bool,
+}
+
+pub struct History {
+    deltas: VecDeque<Delta>,
+    //deltas that are instructions rather than timer ticks
+    instructions: usize,
+    limit: usize,
+}
+
+impl History {
+    //limit is the number of instructions that can be stepped back, 0 turns recording off
+    pub fn new(limit: usize) -> History {
+        History {
+            deltas: VecDeque::new(),
+            instructions: 0,
+            limit,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions
+    }
+
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.instructions = 0;
+    }
+
+    fn record(&mut self, machine: &Machine, tick: bool) {
+        if self.limit == 0 {
+            return;
+        }
+
+        let mut delta = Delta {
+            reg: machine.reg.clone(),
+            frame: machine.frame,
+            cycle: machine.cycle,
+            frame_start_cycle: machine.frame_start_cycle,
+            machine_cycles: machine.machine_cycles,
+            rng_word_pos: machine.rng.get_word_pos(),
+            memory: Vec::new(),
+            pixels: Vec::new(),
+            tick,
+        };
+
+        if !tick {
+            save_writes(machine, &mut delta);
+        }
+
+        self.deltas.push_back(delta);
+        if !tick {
+            self.instructions += 1;
+        }
+
+        while self.instructions > self.limit {
+            if let Some(oldest) = self.deltas.pop_front() {
+                if !oldest.tick {
+                    self.instructions -= 1;
+                }
+            }
+        }
+    }
+
+    pub fn step(&mut self, machine: &mut Machine) -> Result<(), String> {
+        self.record(machine, false);
+        machine.step()
+    }
+
+    pub fn tick_timers(&mut self, machine: &mut Machine) {
+        self.record(machine, true);
+        machine.tick_timers();
+    }
+
+    //a write from outside the machine, like a cheat, kept so stepping back
+    //past it puts the old value back
+    pub fn poke(&mut self, machine: &mut Machine, addr: u16, value: u8) {
+        if self.limit > 0 {
+            self.record(machine, true);
+            if let Some(delta) = self.deltas.back_mut() {
+                delta.memory.push((addr, machine.memory[addr as usize % 4096]));
+            }
+        }
+        machine.poke(addr, value);
+    }
+
+    //undoes the last instruction and any timer tick after it, false when history is empty
+    pub fn step_back(&mut self, machine: &mut Machine) -> bool {
+        while let Some(delta) = self.deltas.pop_back() {
+            machine.reg = delta.reg;
+            machine.frame = delta.frame;
+            machine.cycle = delta.cycle;
+            machine.frame_start_cycle = delta.frame_start_cycle;
+            machine.machine_cycles = delta.machine_cycles;
+            machine.rng.set_word_pos(delta.rng_word_pos);
+
+            for (addr, value) in delta.memory {
+                machine.poke(addr, value);
+            }
+            for (x, y, value) in delta.pixels {
+                machine.display_mem[x as usize][y as usize] = value;
+            }
+
+            if !delta.tick {
+                self.instructions -= 1;
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+fn save_writes(machine: &Machine, delta: &mut Delta) {
+    let reg = &machine.reg;
+    let pc = reg.PC as usize % 4096;
+    let opcode = machine.memory[pc] >> 4;
+    let var_x = (machine.memory[pc] & 0x0f) as usize;
+    let var_kk = machine.memory[(pc + 1) % 4096];
+
+    let mut save_memory = |start: usize, len: usize| {
+        for addr in start..start + len {
+            let addr = addr % 4096;
+            delta.memory.push((addr as u16, machine.memory[addr]));
+        }
+    };
+
+    match (opcode, var_kk) {
+        //call pushes the return address above SP
+        (0x02, _) => save_memory(reg.SP as usize + 2, 2),
+        (0x0f, 0x33) => save_memory(reg.I as usize, 3),
+        (0x0f, 0x55) => save_memory(reg.I as usize, var_x + 1),
+        (0x00, 0xe0) => {
+            for (x, column) in machine.display_mem.iter().enumerate() {
+                for (y, pixel) in column.iter().enumerate() {
+                    if *pixel != 0 {
+                        delta.pixels.push((x as u8, y as u8, *pixel));
+                    }
+                }
+            }
+        },
+        (0x0d, _) => {
+            let var_y = (var_kk >> 4) as usize;
+            let rows = (var_kk & 0x0f) as usize;
+            let x_start = reg.V[var_x] as usize;
+            let y_start = reg.V[var_y] as usize;
+
+            for x in x_start..(x_start + 8).min(128) {
+                for y in y_start..(y_start + rows).min(64) {
+                    delta.pixels.push((x as u8, y as u8, machine.display_mem[x][y]));
+                }
+            }
+        },
+        _ => {},
+    }
+}
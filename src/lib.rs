@@ -0,0 +1,13 @@
+//The emulator core, everything needed to run a machine without a window or a
+//terminal. The chip8 binary builds its frontends on top of it and the C API
+//in capi exposes it to other languages as libchip8.
+pub mod batch;
+pub mod capi;
+pub mod decode;
+pub mod environment;
+pub mod frame;
+pub mod hash;
+pub mod machine;
+pub mod quirks;
+pub mod recompiler;
+pub mod timing;
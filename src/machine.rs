@@ -0,0 +1,695 @@
+//the registers keep the names from the CHIP-8 documentation and the
+//interpreter is written out long hand, so these lints don't apply here
+#![allow(non_snake_case, unused_parens)]
+#![allow(clippy::assign_op_pattern, clippy::collapsible_match, clippy::needless_return)]
+#![allow(clippy::unnecessary_cast, clippy::write_with_newline)]
+use std::fmt::Write;
+use std::io::{Cursor, Read};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::decode::DecodeCache;
+use crate::hash::Fnv1a;
+use crate::quirks::{Quirks, FONT_SIZE};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Registers {
+    pub V: [u8; 16],
+    pub DT: u8,
+    pub ST: u8,
+    pub I: u16,
+    pub SP: u16,
+    pub PC: u16,
+}
+
+impl Default for Registers {
+    fn default() -> Registers {
+        Registers { 
+            I: 0,
+            V: [0; 16],
+            DT: 0,
+            ST: 0,
+            SP: STACK_BASE,
+            PC: 0x200
+        }
+    }
+}
+
+impl Registers {
+    pub fn to_text(&self) -> String {
+        let mut reg_string = "".to_string();
+
+        for i in 0..self.V.len() {
+            write!(reg_string, "V[{}] - {:x}\n",i, self.V[i])
+                .unwrap();
+        }
+
+        reg_string
+
+    }
+}
+
+pub fn reg_state(reg: &mut Registers) {
+    println!("PC - {:x}", reg.PC);
+    println!("SP - {:x}", reg.SP);
+    println!("ST - {:x}", reg.ST);
+    println!("DT - {:x}", reg.DT);
+    println!("I  - {:x}", reg.I);
+
+    for i in 0..16 {
+        println!("V[{}] - {:x}", i, reg.V[i]);
+    }
+}
+
+fn get_bit(byte: u8, pos: u8) -> u8 {
+    if(pos == 8) {
+        return byte & 0x01;
+    } 
+    
+   return ((byte >> (8-pos)) & 0x1) as u8;
+}
+
+fn update_display_mem(display_mem: &mut [[u8; 64]; 128], reg: &mut Registers, X: usize, 
+    Y: usize, memory: &mut [u8; 4096], N: usize) {
+
+    let mut x_pos = reg.V[X] as usize;
+    let mut y_pos = reg.V[Y] as usize;
+    let index = reg.I as usize; 
+
+    for byte in &memory[index..(index+N)] {
+        for i in 1..=8 {
+            //println!("xy value {:x} xor value {:x}",display_mem[x_pos][y_pos], (display_mem[x_pos][y_pos] as u8) ^ (get_bit(*byte, i) as u8));
+            if (display_mem[x_pos][y_pos] == 0x1) && ((display_mem[x_pos][y_pos] as u8) ^ (get_bit(*byte, i) as u8) == 0x0)  {
+                reg.V[0xF] = 1;
+            } else {
+                reg.V[0xF] = 0;
+            }
+            //println!("display VF value {:x}", reg.V[0xF]);
+
+            display_mem[x_pos][y_pos] = (display_mem[x_pos][y_pos] as u8) ^ (get_bit(*byte, i) as u8);
+            //println!("display xor result {:x}", display_mem[x_pos][y_pos]);
+            x_pos = x_pos+1;
+        }
+        y_pos = y_pos+1;
+        x_pos = reg.V[X] as usize;
+    }
+}
+//the font has to fit below the ROM, which loads at 200
+pub fn check_font_address(address: u16) -> Result<(), String> {
+    if address as usize + FONT_SIZE > 0x200 {
+        return Err(format!("font at {:x} would overlap the ROM at 200", address));
+    }
+    Ok(())
+}
+
+fn init_memory(chp8_code: &[u8], memory: &mut [u8; 4096], quirks: &Quirks) -> Result<(), String> {
+    check_font_address(quirks.font_address)?;
+    if chp8_code.len() > MAX_ROM_SIZE {
+        return Err(format!("ROM is {} bytes, at most {} fit", chp8_code.len(), MAX_ROM_SIZE));
+    }
+
+    let font_address = quirks.font_address as usize;
+    memory[font_address..(font_address + FONT_SIZE)].copy_from_slice(&quirks.font.glyphs());
+
+    let mut pos = 0;
+    
+    for i in &mut memory[0x200..(0x200 + chp8_code.len())] { 
+        *i = chp8_code[pos];
+        pos = pos+1;
+    }
+    Ok(())
+}
+
+//the ROM loads at 200 and may fill the rest of memory
+pub const MAX_ROM_SIZE: usize = 4096 - 0x200;
+
+//SP when nothing is on the stack, calls push upwards from here to the end of memory
+pub const STACK_BASE: u16 = 0xfa0;
+
+//native CHIP-8 resolution, display_mem is sized for the 128x64 hires mode
+pub const DISPLAY_WIDTH: u32 = 64;
+pub const DISPLAY_HEIGHT: u32 = 32;
+
+const STATE_MAGIC: &[u8; 4] = b"C8S3";
+
+//magic, memory, V, DT, ST, I, SP, PC, keys, frame, cycle, frame start cycle,
+//machine cycles, packed display, rng seed/stream/position
+pub const STATE_SIZE: usize = 4 + 4096 + 16 + 2 + 6 + 2 + 8 + 8 + 8 + 8 + (128 * 64 / 8) + 32 + 8 + 16;
+
+//memory, registers and framebuffer of one CHIP-8, independent of any frontend
+pub struct Machine {
+    pub memory: [u8; 4096],
+    pub reg: Registers,
+    pub display_mem: [[u8; 64]; 128],
+    //keys held down this instruction, bit n for key n
+    pub keys: u16,
+    //number of 60 Hz frames run so far
+    pub frame: u64,
+    //number of instructions executed so far
+    pub cycle: u64,
+    //value of cycle when the current frame started
+    pub frame_start_cycle: u64,
+    //time taken so far in COSMAC VIP machine cycles, only counted with VIP timing
+    pub machine_cycles: u64,
+    //Cxkk random numbers, seeded so runs can be replayed exactly
+    pub rng: ChaCha8Rng,
+    pub font_address: u16,
+    //Dxyn only draws as the first instruction of a frame
+    pub vblank_wait: bool,
+    pub decode_cache: DecodeCache,
+}
+
+impl Machine {
+    //fails when the ROM doesn't fit or the font would overlap it
+    pub fn new(chp8_code: &[u8], seed: u64, quirks: &Quirks) -> Result<Machine, String> {
+        let mut memory = [0; 4096];
+        init_memory(chp8_code, &mut memory, quirks)?;
+
+        Ok(Machine {
+            memory,
+            reg: Registers::default(),
+            display_mem: [[0u8; 64]; 128],
+            keys: 0,
+            frame: 0,
+            cycle: 0,
+            frame_start_cycle: 0,
+            machine_cycles: 0,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            font_address: quirks.font_address,
+            vblank_wait: quirks.vblank_wait,
+            decode_cache: DecodeCache::new(true),
+        })
+    }
+
+    //called by the frontend at 60 Hz
+    pub fn tick_timers(&mut self) {
+        self.reg.DT = self.reg.DT.saturating_sub(1);
+        self.reg.ST = self.reg.ST.saturating_sub(1);
+        self.frame += 1;
+        self.frame_start_cycle = self.cycle;
+    }
+
+    //covers everything that affects future execution, used to detect replay desyncs
+    pub fn state_hash(&self) -> u64 {
+        let reg = &self.reg;
+        let mut hasher = Fnv1a::new();
+
+        hasher.write(&self.memory);
+        hasher.write(&reg.V);
+        hasher.write(&[reg.DT, reg.ST]);
+        hasher.write(&reg.I.to_be_bytes());
+        hasher.write(&reg.SP.to_be_bytes());
+        hasher.write(&reg.PC.to_be_bytes());
+        for column in self.display_mem.iter() {
+            hasher.write(column);
+        }
+        hasher.write(&self.rng.get_seed());
+        hasher.write(&self.rng.get_stream().to_be_bytes());
+        hasher.write(&self.rng.get_word_pos().to_be_bytes());
+
+        hasher.finish()
+    }
+
+    //serialized machine state for rewind and save states, display packed 8 pixels a byte
+    pub fn save_state(&self) -> Vec<u8> {
+        let reg = &self.reg;
+        let mut data = Vec::with_capacity(STATE_SIZE);
+
+        data.extend_from_slice(STATE_MAGIC);
+        data.extend_from_slice(&self.memory);
+        data.extend_from_slice(&reg.V);
+        data.push(reg.DT);
+        data.push(reg.ST);
+        data.write_u16::<BigEndian>(reg.I).unwrap();
+        data.write_u16::<BigEndian>(reg.SP).unwrap();
+        data.write_u16::<BigEndian>(reg.PC).unwrap();
+        data.write_u16::<BigEndian>(self.keys).unwrap();
+        data.write_u64::<BigEndian>(self.frame).unwrap();
+        data.write_u64::<BigEndian>(self.cycle).unwrap();
+        data.write_u64::<BigEndian>(self.frame_start_cycle).unwrap();
+        data.write_u64::<BigEndian>(self.machine_cycles).unwrap();
+
+        for column in self.display_mem.iter() {
+            for pixels in column.chunks(8) {
+                let mut byte = 0;
+                for (bit, pixel) in pixels.iter().enumerate() {
+                    byte |= (*pixel & 1) << (7 - bit);
+                }
+                data.push(byte);
+            }
+        }
+
+        data.extend_from_slice(&self.rng.get_seed());
+        data.write_u64::<BigEndian>(self.rng.get_stream()).unwrap();
+        data.write_u128::<BigEndian>(self.rng.get_word_pos()).unwrap();
+
+        data
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        if data.len() != STATE_SIZE || &data[..4] != STATE_MAGIC {
+            return Err("not a chip8 save state".to_string());
+        }
+
+        //length is checked above so none of the reads below can fail
+        let mut cursor = Cursor::new(&data[4..]);
+        let reg = &mut self.reg;
+
+        cursor.read_exact(&mut self.memory).unwrap();
+        cursor.read_exact(&mut reg.V).unwrap();
+        reg.DT = cursor.read_u8().unwrap();
+        reg.ST = cursor.read_u8().unwrap();
+        reg.I = cursor.read_u16::<BigEndian>().unwrap();
+        reg.SP = cursor.read_u16::<BigEndian>().unwrap();
+        reg.PC = cursor.read_u16::<BigEndian>().unwrap();
+        self.keys = cursor.read_u16::<BigEndian>().unwrap();
+        self.frame = cursor.read_u64::<BigEndian>().unwrap();
+        self.cycle = cursor.read_u64::<BigEndian>().unwrap();
+        self.frame_start_cycle = cursor.read_u64::<BigEndian>().unwrap();
+        self.machine_cycles = cursor.read_u64::<BigEndian>().unwrap();
+
+        for column in self.display_mem.iter_mut() {
+            for pixels in column.chunks_mut(8) {
+                let byte = cursor.read_u8().unwrap();
+                for (bit, pixel) in pixels.iter_mut().enumerate() {
+                    *pixel = (byte >> (7 - bit)) & 1;
+                }
+            }
+        }
+
+        let mut seed = [0; 32];
+        cursor.read_exact(&mut seed).unwrap();
+        self.rng = ChaCha8Rng::from_seed(seed);
+        self.rng.set_stream(cursor.read_u64::<BigEndian>().unwrap());
+        self.rng.set_word_pos(cursor.read_u128::<BigEndian>().unwrap());
+
+        self.decode_cache.clear();
+
+        Ok(())
+    }
+
+    //holds key alone, 0xff for none
+    pub fn set_key(&mut self, key: u8) {
+        self.keys = if key < 16 { 1 << key } else { 0 };
+    }
+
+    pub fn key_down(&self, key: u8) -> bool {
+        key < 16 && self.keys & (1 << key) != 0
+    }
+
+    //the lowest key held down, 0xff when nothing is pressed
+    pub fn held_key(&self) -> u8 {
+        if self.keys == 0 { 0xff } else { self.keys.trailing_zeros() as u8 }
+    }
+
+    //writes from outside the machine go through here so the decode cache
+    //doesn't keep running the old instruction
+    pub fn poke(&mut self, addr: u16, value: u8) {
+        self.memory[addr as usize % 4096] = value;
+        self.decode_cache.invalidate(addr);
+    }
+
+    //the instruction at addr, wrapping at the end of memory
+    pub fn fetch(&self, addr: u16) -> u16 {
+        let addr = addr as usize % 4096;
+        ((self.memory[addr] as u16) << 8) | self.memory[(addr + 1) % 4096] as u16
+    }
+
+    //fetch, decode and execute a single instruction. Err when it can't run,
+    //an unknown opcode or memory or display access out of range, with the
+    //machine as it was before it apart from the cycle count
+    pub fn step(&mut self) -> Result<(), String> {
+        let keys = self.keys;
+        let key_down = |key: u8| key < 16 && keys & (1 << key) != 0;
+        let held_key = self.held_key();
+        let reg = &mut self.reg;
+        let memory = &mut self.memory;
+        let display_mem = &mut self.display_mem;
+        let rng = &mut self.rng;
+        let decode_cache = &mut self.decode_cache;
+        let font_address = self.font_address;
+        let waiting_for_vblank = self.vblank_wait && self.cycle != self.frame_start_cycle;
+        self.cycle += 1;
+
+        let decoded = decode_cache.get(memory, reg.PC);
+        let instruction = decoded.instruction;
+        let fail = |reason: &str| Err(format!("{:04x} at {:03x} {}", instruction, reg.PC, reason));
+        let opcode = decoded.opcode;
+
+        let var_nnn = decoded.nnn;
+        let var_x = decoded.x;
+        let var_kk = decoded.kk;
+        let var_y = decoded.y;
+        let var_z = decoded.n;
+
+        match opcode {
+            0x00 => {
+                if var_kk == 0xe0 {
+                    //println!("clear screen");
+                    //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} Vx: {:x} Vy: {:x}", instruction,
+                    //    reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
+
+                    *display_mem = [[0u8; 64]; 128];
+
+                    reg.PC = reg.PC + 2;
+                } else if var_kk == 0xee {
+                    if reg.SP <= STACK_BASE {
+                        return fail("returns with an empty stack");
+                    }
+                    if reg.SP as usize + 1 >= memory.len() {
+                        return fail("returns with the stack pointer outside memory");
+                    }
+                    reg.PC = ((memory[reg.SP as usize] as u16) << 8) | (memory[(reg.SP+1) as usize] as u16);
+                    //println!("ret to {:x}", reg.PC);
+                    reg.SP = reg.SP - 2;
+                } else {
+                    reg.PC = reg.PC + 2;
+                }
+            },
+            0x01 => {
+                reg.PC = var_nnn;
+                //break;
+            },
+            0x02 => {
+                
+                //something wonky here....
+                //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} memory[SP]: {:x}{:x}", instruction,
+                //    reg.SP, reg.PC, memory[reg.SP as usize], memory[(reg.SP+1) as usize]);
+
+                if reg.SP as usize + 3 >= memory.len() {
+                    return fail("overflows the stack");
+                }
+
+                //increment stack pointah 
+                reg.SP = reg.SP + 2;
+               
+                memory[reg.SP as usize] = ((reg.PC+2) >> 8) as u8;
+                memory[(reg.SP + 1) as usize] =  (reg.PC+2) as u8;
+                decode_cache.invalidate(reg.SP);
+                decode_cache.invalidate(reg.SP + 1);
+                //println!("SP: {:x} PC: {:0>8x} memory[SP]: {:x}{:x}", 
+                //    reg.SP, reg.PC, memory[reg.SP as usize], memory[(reg.SP+1) as usize]);  
+
+                reg.PC = var_nnn; 
+            },
+            0x03 => {
+                //println!("pc {:x} var_x {:x} var_kk {:x}", pc, reg.V[var_x as usize], var_kk);
+                if reg.V[var_x as usize] == var_kk {
+                    reg.PC = reg.PC + 4;
+                } else {
+                    reg.PC = reg.PC + 2;
+                }
+            },
+            0x04 => {
+                if reg.V[var_x as usize] != var_kk {
+                    reg.PC = reg.PC + 4;
+                } else {
+                    reg.PC = reg.PC + 2;
+                }
+            },
+            0x05 => {
+                if var_x == var_y {
+                    reg.PC = reg.PC + 4;
+                } else {
+                    reg.PC = reg.PC + 2;
+                }
+            },
+            0x06 => {
+                reg.V[(var_x as usize)] = var_kk; 
+                //println!("V {:x} = {:x}", var_x, reg.V[(var_x as usize)]);
+                reg.PC = reg.PC + 2;
+            },
+            0x07 => {                
+                //println!("old value for V[{}] is {:x} and kk {:x}", var_x, reg.V[var_x as usize], var_kk);
+                reg.V[var_x as usize] = reg.V[var_x as usize].wrapping_add(var_kk);
+
+                reg.PC = reg.PC + 2;
+            },
+            0x08 => {
+                match var_z {
+                    0x00 => {
+                        reg.V[var_x as usize] = reg.V[var_y as usize];
+                        reg.PC = reg.PC + 2;
+                    },
+                    0x01 => {
+                        reg.V[var_x as usize] = reg.V[var_x as usize] | reg.V[var_y as usize];
+                        reg.PC = reg.PC + 2; 
+                    },
+                    0x02 => {
+                        reg.V[var_x as usize] = reg.V[var_x as usize] & reg.V[var_y as usize];
+                        reg.PC = reg.PC + 2;
+                    },
+                    0x03 => {
+                        reg.V[var_x as usize] = reg.V[var_x as usize] ^ reg.V[var_y as usize];
+                        reg.PC = reg.PC + 2;
+                    },
+                    0x04 => {
+                        //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} x-value {} y-value {}", instruction,
+                        //        reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
+                        
+                        let v_x = reg.V[var_x as usize] as u16;
+                        let v_y = reg.V[var_y as usize] as u16;
+
+                        //let temp: u16 = reg.V[var_x as usize].wrapping_add(reg.V[var_y as usize]) as u16;
+                        
+                        let temp = v_x.wrapping_add(v_y);
+                        let temp2 = (reg.V[var_x as usize] as u32 + reg.V[var_y as usize] as u32) as u32;
+
+                        reg.V[var_x as usize] = temp as u8;
+
+                        //println!("sum of operation {} {}", temp, temp2);
+
+                        if temp2 > 255 {
+                            reg.V[0xF] = 1;
+                        } else {
+                            reg.V[0xF] = 0;
+                        }
+                        
+                        reg.PC = reg.PC + 2;
+
+                        //println!("8x4 VF value {}", reg.V[0xF]);
+                    },
+                    0x05 => {
+                        //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} x-value {} y-value {}", instruction,
+                        //        reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
+
+                        //println!("8x5 compare {}", reg.V[var_x as usize] > reg.V[var_y as usize]);
+                        let v_x = reg.V[var_x as usize];
+                        let v_y = reg.V[var_y as usize];
+
+                        let temp = v_x.wrapping_sub(v_y);
+
+                        reg.V[var_x as usize] = temp;
+
+                        if v_x >= v_y {
+                            reg.V[0xF] = 1;
+                        } else {
+                            reg.V[0xF] = 0;
+                        }
+
+                        reg.PC = reg.PC + 2;
+                        //println!("8x5 VF value {} and vx {} and vy {} and v[x] {}", reg.V[0xF], v_x, v_y, reg.V[var_x as usize]);
+                    },
+                    0x06 => {
+                        //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} x-value {} y-value {}", instruction,
+                        //        reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
+
+                        //println!("lsb value: {:x}", reg.V[var_x as usize] & 1);
+                        let v_x = reg.V[var_x as usize];
+
+                        let temp = reg.V[var_x as usize] / 2;
+                        reg.V[var_x as usize] = temp;
+
+                        if (v_x & 1) == 1 {
+                            reg.V[0xF] = 1;
+                        } else {
+                            reg.V[0xF] = 0;
+                        }
+                        reg.PC = reg.PC + 2;
+                        //println!("8x6 VF value {}", reg.V[0xF]);
+                    },
+                    0x07 => {
+                        //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} x-value {} y-value {}", instruction,
+                        //       reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
+                        let v_x = reg.V[var_x as usize];
+                        let v_y = reg.V[var_y as usize];
+
+                        let temp = reg.V[var_y as usize].wrapping_sub(reg.V[var_x as usize]);
+                        reg.V[var_x as usize] = temp;
+
+                        if v_y >= v_x {
+                            reg.V[0xF] = 1;
+                        } else {
+                            reg.V[0xF] = 0;
+                        }
+                        
+                        reg.PC = reg.PC + 2;
+                        //println!("8x7 VF value {}", reg.V[0xF]);
+                    },
+                    0x0E => {
+                        //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} x-value {} y-value {}", instruction,
+                        //        reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
+
+                        //println!("8xE and operation {:x}", reg.V[var_x as usize] & 0x80);
+                        let v_x = reg.V[var_x as usize];
+                        
+                        let temp = reg.V[var_x as usize].wrapping_mul(2);
+                        reg.V[var_x as usize] = temp;
+
+                        if (v_x & 0x80) == 0x80 {
+                            reg.V[0xF] = 1;
+                        } else {
+                            reg.V[0xF] = 0;
+                        }
+                        
+                        reg.PC = reg.PC + 2;
+                        //println!("8xE VF value {}", reg.V[0xF]);
+                    },
+                    _ => return fail("is not an instruction"),
+                }
+            },
+            0x09 => {
+                if reg.V[var_x as usize] != reg.V[var_y as usize] {
+                    reg.PC = reg.PC + 4;
+                } else {
+                    reg.PC = reg.PC + 2;
+                }
+            },
+            0x0a => {
+                reg.I = var_nnn;
+                reg.PC = reg.PC + 2;
+                //println!("opcode {:x} variable reg.I {:x}", opcode, reg.I);
+            },
+            0x0b => {
+                reg.PC = var_nnn + (reg.V[0] as u16);
+            },
+            0x0c => {
+                let rnd: u8 = rng.gen();
+                reg.V[var_x as usize] = rnd & var_kk;
+
+                reg.PC = reg.PC + 2;
+            },
+            0x0d => {
+                //only the first instruction of a frame comes straight after the
+                //interrupt, anywhere else the draw is retried next frame
+                if waiting_for_vblank {
+                    return Ok(());
+                }
+
+                if reg.I as usize + var_z as usize > memory.len() {
+                    return fail("draws a sprite from past the end of memory");
+                }
+                if reg.V[var_x as usize] as usize + 8 > display_mem.len()
+                    || reg.V[var_y as usize] as usize + var_z as usize > display_mem[0].len() {
+                    return fail("draws off the display");
+                }
+
+                reg.PC = reg.PC + 2;
+
+                //drawn to the window on the next frame boundary
+                update_display_mem(display_mem, reg, (var_x as usize), 
+                    (var_y as usize), memory, (var_z as usize));
+                
+                //println!("display at X: {} Y: {} the following: {:b}", 
+                //    reg.V[(var_x as usize)], reg.V[(var_y as usize)], 
+                //    memory[(reg.I as usize)]);
+            },
+            0x0e => {
+                if var_kk == 0x9e {
+                    if key_down(reg.V[var_x as usize]) {
+                        reg.PC = reg.PC + 4;
+                    } else {
+                        reg.PC = reg.PC + 2;
+                    }
+                } else if var_kk == 0xa1 {
+                    if !key_down(reg.V[var_x as usize]) {
+                        reg.PC = reg.PC + 4;
+                    } else {
+                        reg.PC = reg.PC + 2;
+                    }
+                } else {
+                    return fail("is not an instruction");
+                }
+            },
+            0x0f => {
+                match var_kk {
+                    0x07 => {
+                        reg.V[var_x as usize] = reg.DT;
+                        reg.PC = reg.PC + 2;
+                    },
+                    0x0a => {
+                        reg.V[var_x as usize] = held_key;
+                        if held_key != 0xff {
+                            reg.PC = reg.PC + 2;
+                        }
+                    },
+                    0x15 => {
+                        reg.DT = reg.V[var_x as usize];
+                        reg.PC = reg.PC + 2;
+                    },
+                    0x18 => {
+                        reg.ST = reg.V[var_x as usize];
+                        reg.PC = reg.PC + 2;
+                    },
+                    0x1e => {
+                        reg.I = reg.I.wrapping_add(reg.V[var_x as usize] as u16);
+                        reg.PC = reg.PC + 2;
+                    },
+                    0x29 => {
+                        //each glyph is 5 bytes, only the low nibble picks one
+                        let digit = (reg.V[var_x as usize] & 0x0f) as u16;
+                        reg.I = font_address + digit * 5;
+                        reg.PC = reg.PC + 2;
+                    },
+                    0x33 => {
+                        if reg.I as usize + 2 >= memory.len() {
+                            return fail("writes past the end of memory");
+                        }
+
+                        let mut dec: u8 = reg.V[var_x as usize];
+
+                        memory[(reg.I+2) as usize] = dec % 10;
+                        dec = dec / 10;
+
+                        memory[(reg.I+1) as usize] = dec % 10;
+                        dec = dec / 10;
+
+                        memory[reg.I as usize] = dec % 10;
+
+                        for i in 0..3 {
+                            decode_cache.invalidate(reg.I + i);
+                        }
+
+                        reg.PC = reg.PC + 2;
+                    },
+                    0x55 => {
+                        if reg.I as usize + var_x as usize >= memory.len() {
+                            return fail("writes past the end of memory");
+                        }
+                        for i in 0..=var_x {
+                            memory[(reg.I + (i as u16)) as usize] = reg.V[i as usize];
+                            decode_cache.invalidate(reg.I + (i as u16));
+                        }
+                        reg.PC = reg.PC + 2;
+                    },
+                    0x65 => {
+                        if reg.I as usize + var_x as usize >= memory.len() {
+                            return fail("reads past the end of memory");
+                        }
+                        for i in 0..=var_x {
+                            reg.V[i as usize] = memory[(reg.I + (i as u16)) as usize];
+                        }
+                        reg.PC = reg.PC + 2;
+                    },
+                    _ => return fail("is not an instruction"),
+                }
+            },
+            _ => { 
+                reg.PC = reg.PC + 2;
+            },
+        }
+
+        Ok(())
+    }
+}
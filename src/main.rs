@@ -1,597 +1,272 @@
 use std::env;
 use std::fs;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::thread;
-use std::io;
-use std::fmt::Write;
-use ratatui::layout::Alignment;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
+use std::time::Duration;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::render::WindowCanvas;
-use rand::Rng;
+use sdl2::video::FullscreenType;
+use sdl2::EventPump;
 
 use ratatui::{
     crossterm::event::{self, Event as tuiEvent},
-    text::{Line, Text},
-    style::Stylize,
-    symbols::border,
-    widgets:: {
-        block::{Position, Title},
-        Block, Paragraph, Widget,
-    },
-    Frame,
+    DefaultTerminal, Frame,
 };
 
-struct Registers {
-    V: [u8; 16],
-    DT: u8,
-    ST: u8,
-    I: u16,
-    SP: u16,
-    PC: u16,
-}
-
-impl Default for Registers {
-    fn default() -> Registers {
-        Registers { 
-            I: 0,
-            V: [0; 16],
-            DT: 0,
-            ST: 0,
-            SP: 0xfa0,
-            PC: 0x200
-        }
-    }
-}
-
-impl Registers {
-    fn to_text(&self) -> String {
-        let mut reg_string = "".to_string();
-
-        for i in 0..self.V.len() {
-            write!(reg_string, "V[{}] - {:x}\n",i, self.V[i])
-                .unwrap();
-        }
-
-        reg_string
-
-    }
-}
+use chip8::{batch, decode, environment, frame, hash, machine, quirks, recompiler, timing};
+
+mod backenddiff;
+mod bench;
+mod capture;
+mod cfg;
+mod cheats;
+mod config;
+mod coverage;
+mod debugger;
+mod disasm;
+mod display;
+mod emulation;
+mod gdbstub;
+mod governor;
+mod headless;
+mod history;
+mod movie;
+mod phosphor;
+mod profiler;
+mod recorder;
+mod rewind;
+mod screenshot;
+mod session;
+mod terminal;
+mod trace;
+mod tracediff;
+
+use config::{Config, Frontend};
+use display::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
+use emulation::Command;
+use governor::FRAME_TIME;
+use phosphor::Phosphor;
 
-const chip8_sprites: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80  // F
-];
-
-fn reg_state(reg: &mut Registers) {
-    println!("PC - {:x}", reg.PC);
-    println!("SP - {:x}", reg.SP);
-    println!("ST - {:x}", reg.ST);
-    println!("DT - {:x}", reg.DT);
-    println!("I  - {:x}", reg.I);
-
-    for i in 0..16 {
-        println!("V[{}] - {:x}", i, reg.V[i]);
-    }
-}
-
-fn get_bit(byte: u8, pos: u8) -> u8 {
-    if(pos == 8) {
-        return byte & 0x01;
-    } 
-    
-   return ((byte >> (8-pos)) & 0x1) as u8;
-}
-
-fn update_display_mem(display_mem: &mut [[u8; 64]; 128], reg: &mut Registers, X: usize, 
-    Y: usize, memory: &mut [u8; 4096], N: usize) {
-
-    let mut x_pos = reg.V[X] as usize;
-    let mut y_pos = reg.V[Y] as usize;
-    let index = reg.I as usize; 
-
-    for byte in &memory[index..(index+N)] {
-        for i in 1..=8 {
-            //println!("xy value {:x} xor value {:x}",display_mem[x_pos][y_pos], (display_mem[x_pos][y_pos] as u8) ^ (get_bit(*byte, i) as u8));
-            if (display_mem[x_pos][y_pos] == 0x1) && ((display_mem[x_pos][y_pos] as u8) ^ (get_bit(*byte, i) as u8) == 0x0)  {
-                reg.V[0xF] = 1;
-            } else {
-                reg.V[0xF] = 0;
-            }
-            //println!("display VF value {:x}", reg.V[0xF]);
-
-            display_mem[x_pos][y_pos] = (display_mem[x_pos][y_pos] as u8) ^ (get_bit(*byte, i) as u8);
-            //println!("display xor result {:x}", display_mem[x_pos][y_pos]);
-            x_pos = x_pos+1;
-        }
-        y_pos = y_pos+1;
-        x_pos = reg.V[X] as usize;
-    }
-}
+fn main() {
+    let args: Vec<String> = env::args().collect();
 
-fn display(canvas: &mut WindowCanvas, reg: &mut Registers, X: usize, 
-    Y: usize, memory: &mut [u8; 4096], N: usize) {
-   
-   let mut x_pos = (reg.V[X] as i32)*8;
-   let mut y_pos = (reg.V[Y] as i32)*8;
-   let index = reg.I as usize; 
-   //println!("display at X: {} and Y: {} this sprite info: {:x?}", reg.V[X], reg.V[Y],
-   //     &memory[index..(index+N)]);
-    
-    for byte in &memory[index..(index+N)] {
-        for i in 1..=8 {
-            if get_bit(*byte, i) == 1 {
-                canvas.set_draw_color(Color::RGB(255,255,25));
-                canvas.fill_rect(Rect::new(x_pos, y_pos, 8, 8));
-                canvas.present();
-            } else {
-                canvas.set_draw_color(Color::RGB(0,0,255));
-                canvas.fill_rect(Rect::new(x_pos, y_pos, 8, 8));
-                canvas.present();
-            }
-            thread::sleep_ms(1);
-            x_pos = x_pos + 8;
-        }
-        x_pos = (reg.V[X] as i32)*8;
-        //x_pos = x_pos*8;
-        y_pos = y_pos + 8;
+    //tools that don't run a frontend
+    let tool_result = match args.get(1).map(String::as_str) {
+        Some("trace-diff") => Some(tracediff::run(&args[2..]).map(|matched| if matched { 0 } else { 2 })),
+        Some("cfg") => Some(cfg::run(&args[2..]).map(|_| 0)),
+        Some("bench") => Some(bench::run(&args[2..]).map(|_| 0)),
+        Some("batch") => Some(batch::run(&args[2..]).map(|_| 0)),
+        Some("env") => Some(environment::run(&args[2..]).map(|_| 0)),
+        Some("backend-diff") => Some(backenddiff::run(&args[2..]).map(|matched| if matched { 0 } else { 2 })),
+        _ => None,
+    };
+
+    match tool_result {
+        Some(Ok(code)) => std::process::exit(code),
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+        None => {},
     }
-}
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let chp8_file = &args[1];
+    let config = config::parse_args(&args)
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
     
-    let chp8_file_contents = fs::read(chp8_file)
+    let chp8_file_contents = fs::read(&config.rom_path)
         .expect("Something went wrong with reading the file");
 
 
-    println!("{:#?}", args);
-    
-    chp8_execute(&chp8_file_contents);
-}
+    let result = match (&config.gdb, config.frontend) {
+        (Some(address), _) => gdbstub::serve(&chp8_file_contents, &config, address),
+        (None, Frontend::Sdl) => chp8_execute(&chp8_file_contents, &config),
+        (None, Frontend::Terminal) => terminal::chp8_execute_terminal(&chp8_file_contents, &config),
+        (None, Frontend::Headless) => headless::chp8_execute_headless(&chp8_file_contents, &config),
+    };
 
-fn init_memory(chp8_code: &Vec<u8>, memory: &mut [u8; 4096]) {
-    let mut pos = 0;
-    for i in &mut memory[0x0..0x50] { 
-        *i = chip8_sprites[pos];
-        pos = pos + 1; 
-    }
-
-    pos = 0;
-    
-    for i in &mut memory[0x200..(0x200 + chp8_code.len())] { 
-        *i = chp8_code[pos];
-        pos = pos+1;
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
     }
 }
 
-fn chp8_execute(chp8_code: &Vec<u8>) -> Result<(), String> {    
-    //Memory creation and init
-    let mut memory: [u8; 4096];
-    memory = [0; 4096];
-    init_memory(&chp8_code, &mut memory);
-
-    //Display Memory
-    let mut display_mem = [[0u8; 64]; 128];
+fn keypad_index(keycode: Keycode) -> Option<u8> {
+    let index = match keycode {
+        Keycode::Num1 => 0x01,
+        Keycode::Num2 => 0x02,
+        Keycode::Num3 => 0x03,
+        Keycode::Num4 => 0x0c,
+        Keycode::Q => 0x04,
+        Keycode::W => 0x05,
+        Keycode::E => 0x06,
+        Keycode::R => 0x0d,
+        Keycode::A => 0x07,
+        Keycode::S => 0x08,
+        Keycode::D => 0x09,
+        Keycode::F => 0x0e,
+        Keycode::Z => 0x0a,
+        Keycode::X => 0x00,
+        Keycode::C => 0x0b,
+        Keycode::V => 0x0f,
+        _ => return None,
+    };
+
+    Some(index)
+}
 
+fn chp8_execute(chp8_code: &[u8], config: &Config) -> Result<(), String> {    
     //Display Creation and init
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
-    let window = video_subsystem.window("Chip8", 800, 600)
+    let display_config = &config.display;
+    let (window_width, window_height) = display_config.window_size();
+
+    let mut window = video_subsystem.window("Chip8", window_width, window_height)
         .position_centered()
+        .resizable()
         .build()
         .expect("could not initialize video subsystem");
 
+    if display_config.fullscreen {
+        window.set_fullscreen(FullscreenType::Desktop)?;
+    }
+
     let mut canvas = window.into_canvas().build()
         .expect("could not make a canvas");
 
+    //logical size keeps the aspect ratio and letterboxes when the window doesn't match
+    canvas.set_logical_size(DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        .expect("could not set logical size");
+
+    canvas.set_draw_color(display_config.palette.background());
+    canvas.clear();
+    canvas.present();
+
 
-    let mut reg = Registers::default();
-    let mut i = 0;
+    let mut phosphor = Phosphor::new(display_config.phosphor);
+    let mut event_pump = sdl_context.event_pump()?;
+    let (command_sender, commands) = mpsc::channel();
+    let (event_sender, events) = mpsc::channel();
 
     let mut terminal = ratatui::init();
-    
 
-    loop {
-        let pc = reg.PC as usize;    
-        let instruction = ((memory[pc] as u16) << 8) | memory[pc+1] as u16;
-        let opcode = memory[pc] >> 4;
+    //the machine runs on its own thread, this one only looks after the window
+    //and the debug view so drawing them can't slow the game down
+    let (window_result, emulation_result) = thread::scope(|scope| {
+        let emulation = scope.spawn(|| emulation::run(chp8_code, config, commands, event_sender));
 
-        let var_nnn = instruction ^ ((opcode as u16) << 12);
-        let var_x = (var_nnn >> 8) as u8;
-        let var_kk = var_nnn as u8; 
-        let var_y = (var_kk >> 4) as u8;
-        let var_z = ((var_kk << 4) >> 4) as u8;
+        let window_result = run_window(&mut canvas, &mut event_pump, &mut terminal, &mut phosphor,
+            config, &command_sender, &events);
+        let _ = command_sender.send(Command::Quit);
 
-        let mut current_key: u8 = 0xff;
+        let emulation_result = emulation.join()
+            .unwrap_or_else(|_| Err("the emulation thread panicked".to_string()));
+        (window_result, emulation_result)
+    });
 
-        let mut event_pump = sdl_context.event_pump()?;
+    ratatui::restore();
 
+    let summary = emulation_result?;
+    if config.speed.benchmark {
+        println!("{}", summary);
+    }
+
+    window_result
+}
+
+//returns when the window is closed or the emulation thread stops
+fn run_window(canvas: &mut WindowCanvas, event_pump: &mut EventPump, terminal: &mut DefaultTerminal,
+    phosphor: &mut Phosphor, config: &Config, commands: &Sender<Command>, events: &Receiver<emulation::Event>)
+    -> Result<(), String> {
+
+    let mut held_key: Option<u8> = None;
+    let mut debug_lines = Vec::new();
+
+    loop {
         for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit {..} => break,
+            let command = match event {
+                Event::Quit {..} => return Ok(()),
+                Event::KeyDown { keycode: Some(Keycode::F12), .. } => Some(Command::Screenshot),
+                Event::KeyDown { keycode: Some(Keycode::F9), .. } => Some(Command::ToggleRecording),
+                Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => Some(Command::Rewind(true)),
+                Event::KeyUp { keycode: Some(Keycode::Backspace), .. } => Some(Command::Rewind(false)),
+                Event::KeyDown { keycode: Some(Keycode::Tab), .. } => Some(Command::FastForward(true)),
+                Event::KeyUp { keycode: Some(Keycode::Tab), .. } => Some(Command::FastForward(false)),
                 Event::KeyDown { keycode: Some(keycode), .. } => {
-                    match keycode {
-                        Keycode::Num1 => current_key = 0x01,
-                        Keycode::Num2 => current_key = 0x02,
-                        Keycode::Num3 => current_key = 0x03,
-                        Keycode::Num4 => current_key = 0x0c,
-                        Keycode::Q => current_key = 0x04,
-                        Keycode::W => current_key = 0x05,
-                        Keycode::E => current_key = 0x06,
-                        Keycode::R => current_key = 0x0d,
-                        Keycode::A => current_key = 0x07,
-                        Keycode::S => current_key = 0x08,
-                        Keycode::D => current_key = 0x09,
-                        Keycode::F => current_key = 0x0e,
-                        Keycode::Z => current_key = 0x0a,
-                        Keycode::X => current_key = 0x00,
-                        Keycode::C => current_key = 0x0b,
-                        Keycode::V => current_key = 0x0f,
-                        _ => break
-
+                    keypad_index(keycode).map(|index| {
+                        held_key = Some(index);
+                        Command::Key(held_key)
+                    })
+                },
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    if held_key.is_some() && keypad_index(keycode) == held_key {
+                        held_key = None;
+                        Some(Command::Key(None))
+                    } else {
+                        None
                     }
                 },
-                _ => {}
+                _ => None,
+            };
+
+            if let Some(command) = command {
+                if commands.send(command).is_err() {
+                    return Ok(());
+                }
             }
         }
 
-        //render(&mut canvas, Color::RGB(i, 64, 255 - i));
-
-        //::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
-        terminal.draw(|frame: &mut Frame| {
-                let title = Title::from(" CHIP-8 DEBUG ".bold());
-
-                let block = Block::bordered()
-                    .title(title.alignment(Alignment::Center))
-                    .border_set(border::THICK);
-
+        //the debug view takes its keys from the terminal it is drawn in
+        while event::poll(Duration::ZERO).map_err(|e| e.to_string())? {
+            if let tuiEvent::Key(key) = event::read().map_err(|e| e.to_string())? {
+                if commands.send(Command::DebuggerKey(key)).is_err() {
+                    return Ok(());
+                }
+            }
+        }
 
-                frame.render_widget(
-                    Paragraph::new(reg.to_text()).block(block.clone()).alignment(Alignment::Left),
-                    frame.area(),
-                );
+        //the emulation thread sets the pace, when drawing falls behind only
+        //the newest frame is drawn
+        let mut received = match events.recv_timeout(FRAME_TIME) {
+            Ok(event) => vec![event],
+            Err(RecvTimeoutError::Timeout) => Vec::new(),
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        };
+        received.extend(events.try_iter());
+
+        let mut display_mem = None;
+        for event in received {
+            match event {
+                emulation::Event::Frame(frame) => display_mem = Some(frame),
+                emulation::Event::Status(status) => {
+                    canvas.window_mut().set_title(&format!("Chip8 - {}", status))
+                        .map_err(|e| e.to_string())?;
+                },
+                emulation::Event::Debug(lines) => debug_lines = lines,
+                //no audio device yet, the debug view's terminal beeps instead
+                emulation::Event::Sound(true) => {
+                    let mut stdout = io::stdout();
+                    stdout.write_all(b"\x07").and_then(|_| stdout.flush())
+                        .map_err(|e| e.to_string())?;
+                },
+                emulation::Event::Sound(false) => {},
+            }
+        }
 
-                frame.render_widget(
-                    Paragraph::new(reg.to_text()).block(block.clone()).alignment(Alignment::Center),
-                    frame.area(),
-                );
+        if let Some(display_mem) = display_mem {
+            //render(&mut canvas, Color::RGB(i, 64, 255 - i));
+            let intensity = phosphor.update(&display_mem);
+            display::render_frame(canvas, intensity, &config.display.palette);
+        }
 
+        terminal.draw(|frame: &mut Frame| {
+                debugger::render_lines(frame, frame.area(), debug_lines.clone());
             }).expect("failed to draw");
-
-
-        match opcode {
-            0x00 => {
-                if var_kk == 0xe0 {
-                    //println!("clear screen");
-                    //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} Vx: {:x} Vy: {:x}", instruction,
-                    //    reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
-
-                    display_mem = [[0u8; 64]; 128];
-                    canvas.clear();
-
-                    reg.PC = reg.PC + 2;
-                } else if var_kk == 0xee {
-                    reg.PC = ((memory[reg.SP as usize] as u16) << 8) | (memory[(reg.SP+1) as usize] as u16);
-                    //println!("ret to {:x}", reg.PC);
-                    reg.SP = reg.SP - 2;
-                } else {
-                    reg.PC = reg.PC + 2;
-                }
-            },
-            0x01 => {
-                reg.PC = var_nnn;
-                //break;
-            },
-            0x02 => {
-                
-                //something wonky here....
-                //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} memory[SP]: {:x}{:x}", instruction,
-                //    reg.SP, reg.PC, memory[reg.SP as usize], memory[(reg.SP+1) as usize]);
-
-                //increment stack pointah 
-                reg.SP = reg.SP + 2;
-               
-                memory[reg.SP as usize] = ((reg.PC+2) >> 8) as u8;
-                memory[(reg.SP + 1) as usize] =  (reg.PC+2) as u8;
-                //println!("SP: {:x} PC: {:0>8x} memory[SP]: {:x}{:x}", 
-                //    reg.SP, reg.PC, memory[reg.SP as usize], memory[(reg.SP+1) as usize]);  
-
-                reg.PC = var_nnn; 
-            },
-            0x03 => {
-                //println!("pc {:x} var_x {:x} var_kk {:x}", pc, reg.V[var_x as usize], var_kk);
-                if reg.V[var_x as usize] == var_kk {
-                    reg.PC = reg.PC + 4;
-                } else {
-                    reg.PC = reg.PC + 2;
-                }
-            },
-            0x04 => {
-                if reg.V[var_x as usize] != var_kk {
-                    reg.PC = reg.PC + 4;
-                } else {
-                    reg.PC = reg.PC + 2;
-                }
-            },
-            0x05 => {
-                if var_x == var_y {
-                    reg.PC = reg.PC + 4;
-                } else {
-                    reg.PC = reg.PC + 2;
-                }
-            },
-            0x06 => {
-                reg.V[(var_x as usize)] = var_kk; 
-                //println!("V {:x} = {:x}", var_x, reg.V[(var_x as usize)]);
-                reg.PC = reg.PC + 2;
-            },
-            0x07 => {                
-                //println!("old value for V[{}] is {:x} and kk {:x}", var_x, reg.V[var_x as usize], var_kk);
-                reg.V[var_x as usize] = reg.V[var_x as usize].wrapping_add(var_kk);
-
-                reg.PC = reg.PC + 2;
-            },
-            0x08 => {
-                match var_z {
-                    0x00 => {
-                        reg.V[var_x as usize] = reg.V[var_y as usize];
-                        reg.PC = reg.PC + 2;
-                    },
-                    0x01 => {
-                        reg.V[var_x as usize] = reg.V[var_x as usize] | reg.V[var_y as usize];
-                        reg.PC = reg.PC + 2; 
-                    },
-                    0x02 => {
-                        reg.V[var_x as usize] = reg.V[var_x as usize] & reg.V[var_y as usize];
-                        reg.PC = reg.PC + 2;
-                    },
-                    0x03 => {
-                        reg.V[var_x as usize] = reg.V[var_x as usize] ^ reg.V[var_y as usize];
-                        reg.PC = reg.PC + 2;
-                    },
-                    0x04 => {
-                        //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} x-value {} y-value {}", instruction,
-                        //        reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
-                        
-                        let v_x = reg.V[var_x as usize] as u16;
-                        let v_y = reg.V[var_y as usize] as u16;
-
-                        //let temp: u16 = reg.V[var_x as usize].wrapping_add(reg.V[var_y as usize]) as u16;
-                        
-                        let temp = v_x.wrapping_add(v_y);
-                        let temp2 = (reg.V[var_x as usize] as u32 + reg.V[var_y as usize] as u32) as u32;
-
-                        reg.V[var_x as usize] = temp as u8;
-
-                        //println!("sum of operation {} {}", temp, temp2);
-
-                        if temp2 > 255 {
-                            reg.V[0xF] = 1;
-                        } else {
-                            reg.V[0xF] = 0;
-                        }
-                        
-                        reg.PC = reg.PC + 2;
-
-                        //println!("8x4 VF value {}", reg.V[0xF]);
-                    },
-                    0x05 => {
-                        //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} x-value {} y-value {}", instruction,
-                        //        reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
-
-                        //println!("8x5 compare {}", reg.V[var_x as usize] > reg.V[var_y as usize]);
-                        let v_x = reg.V[var_x as usize];
-                        let v_y = reg.V[var_y as usize];
-
-                        let temp = v_x.wrapping_sub(v_y);
-
-                        reg.V[var_x as usize] = temp;
-
-                        if v_x >= v_y {
-                            reg.V[0xF] = 1;
-                        } else {
-                            reg.V[0xF] = 0;
-                        }
-
-                        reg.PC = reg.PC + 2;
-                        //println!("8x5 VF value {} and vx {} and vy {} and v[x] {}", reg.V[0xF], v_x, v_y, reg.V[var_x as usize]);
-                    },
-                    0x06 => {
-                        //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} x-value {} y-value {}", instruction,
-                        //        reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
-
-                        //println!("lsb value: {:x}", reg.V[var_x as usize] & 1);
-                        let v_x = reg.V[var_x as usize];
-                        let v_y = reg.V[var_y as usize];
-
-                        let temp = reg.V[var_x as usize] / 2;
-                        reg.V[var_x as usize] = temp;
-
-                        if (v_x & 1) == 1 {
-                            reg.V[0xF] = 1;
-                        } else {
-                            reg.V[0xF] = 0;
-                        }
-                        reg.PC = reg.PC + 2;
-                        //println!("8x6 VF value {}", reg.V[0xF]);
-                    },
-                    0x07 => {
-                        //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} x-value {} y-value {}", instruction,
-                        //       reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
-                        let v_x = reg.V[var_x as usize];
-                        let v_y = reg.V[var_y as usize];
-
-                        let temp = reg.V[var_y as usize].wrapping_sub(reg.V[var_x as usize]);
-                        reg.V[var_x as usize] = temp;
-
-                        if v_y >= v_x {
-                            reg.V[0xF] = 1;
-                        } else {
-                            reg.V[0xF] = 0;
-                        }
-                        
-                        reg.PC = reg.PC + 2;
-                        //println!("8x7 VF value {}", reg.V[0xF]);
-                    },
-                    0x0E => {
-                        //println!("Instruction: {:x} SP: {:x} PC: {:0>8x} x-value {} y-value {}", instruction,
-                        //        reg.SP, reg.PC, reg.V[var_x as usize], reg.V[var_y as usize]);
-
-                        //println!("8xE and operation {:x}", reg.V[var_x as usize] & 0x80);
-                        let v_x = reg.V[var_x as usize];
-                        let v_y = reg.V[var_y as usize];
-                        
-                        let temp = reg.V[var_x as usize].wrapping_mul(2);
-                        reg.V[var_x as usize] = temp;
-
-                        if (v_x & 0x80) == 0x80 {
-                            reg.V[0xF] = 1;
-                        } else {
-                            reg.V[0xF] = 0;
-                        }
-                        
-                        reg.PC = reg.PC + 2;
-                        //println!("8xE VF value {}", reg.V[0xF]);
-                    },
-                    _ => {
-                        reg.PC = reg.PC + 2;
-                    },
-                }
-            },
-            0x09 => {
-                if reg.V[var_x as usize] != reg.V[var_y as usize] {
-                    reg.PC = reg.PC + 4;
-                } else {
-                    reg.PC = reg.PC + 2;
-                }
-            },
-            0x0a => {
-                reg.I = var_nnn;
-                reg.PC = reg.PC + 2;
-                //println!("opcode {:x} variable reg.I {:x}", opcode, reg.I);
-            },
-            0x0b => {
-                reg.PC = var_nnn + (reg.V[0] as u16);
-            },
-            0x0c => {
-                let mut rng = rand::thread_rng();
-
-                let rnd: u8 = rng.gen();
-                reg.V[var_x as usize] = rnd & var_kk;
-
-                reg.PC = reg.PC + 2;
-            },
-            0x0d => {
-                reg.PC = reg.PC + 2;
-
-                update_display_mem(&mut display_mem, &mut reg, (var_x as usize), 
-                    (var_y as usize), &mut memory, (var_z as usize));
-                
-                display(&mut canvas, &mut reg, (var_x as usize), 
-                    (var_y as usize), &mut memory, (var_z as usize)); 
-                
-                //println!("display at X: {} Y: {} the following: {:b}", 
-                //    reg.V[(var_x as usize)], reg.V[(var_y as usize)], 
-                //    memory[(reg.I as usize)]);
-            },
-            0x0e => {
-                if var_kk == 0x9e {
-                    if reg.V[var_x as usize] == current_key {
-                        reg.PC = reg.PC + 4;
-                    } else {
-                        reg.PC = reg.PC + 2;
-                    }
-                } else if var_kk == 0xa1 {
-                    if reg.V[var_x as usize] != current_key {
-                        reg.PC = reg.PC + 4;
-                    } else {
-                        reg.PC = reg.PC + 2;
-                    }
-                } else {
-                    reg.PC = reg.PC + 2;
-                }
-            },
-            0x0f => {
-                match var_kk {
-                    0x07 => {
-                        reg.V[var_x as usize] = reg.DT;
-                        reg.PC = reg.PC + 2;
-                    },
-                    0x0a => {
-                        reg.V[var_x as usize] = current_key;
-                        if current_key != 0xff {
-                            reg.PC = reg.PC + 2;
-                        }
-                    },
-                    0x15 => {
-                        reg.DT = reg.V[var_x as usize];
-                        reg.PC = reg.PC + 2;
-                    },
-                    0x18 => {
-                        reg.ST = reg.V[var_x as usize];
-                        reg.PC = reg.PC + 2;
-                    },
-                    0x1e => {
-                        reg.I = reg.I + (reg.V[var_x as usize] as u16);
-                        reg.PC = reg.PC + 2;
-                    },
-                    0x29 => {
-                        reg.I = memory[(var_x*5) as usize] as u16;
-                        reg.PC = reg.PC + 2;
-                    },
-                    0x33 => {
-                        let mut dec: u8 = reg.V[var_x as usize];
-
-                        memory[(reg.I+2) as usize] = dec % 10;
-                        dec = dec / 10;
-
-                        memory[(reg.I+1) as usize] = dec % 10;
-                        dec = dec / 10;
-
-                        memory[reg.I as usize] = dec % 10;
-
-                        reg.PC = reg.PC + 2;
-                    },
-                    0x55 => {
-                        for i in 0..=var_x {
-                            memory[(reg.I + (i as u16)) as usize] = reg.V[i as usize];
-                        }
-                        reg.PC = reg.PC + 2;
-                    },
-                    0x65 => {
-                        for i in 0..=var_x {
-                            reg.V[i as usize] = memory[(reg.I + (i as u16)) as usize];
-                        }
-                        reg.PC = reg.PC + 2;
-                    },
-                    _ => {
-                        println!("UNKNOWN 0xF INSTRUCTION {:x} at position {:x}", instruction, reg.PC);
-                        reg.PC = reg.PC + 2;
-                    }
-                }
-            },
-            _ => { 
-                reg.PC = reg.PC + 2;
-            },
-        }
     }
-
 }
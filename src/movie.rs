@@ -0,0 +1,299 @@
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::config::parse_bool;
+use crate::machine::{self, Machine};
+use crate::quirks::{FontSet, Quirks};
+use crate::recompiler::Backend;
+use crate::timing::Timing;
+
+//Movie files are plain text: a header of "key value" lines, then "frames",
+//then one line per 60 Hz frame holding the key held during that frame
+//("-" for none) and, every hash-interval frames, the state hash after it.
+//The header has every setting that changes how the ROM runs, a replay has to
+//use the same ones.
+//
+//  chip8-movie 2
+//  rom 8c2d6e1f0a9b3c47
+//  seed 1234
+//  font schip
+//  font-address 000
+//  timing fixed
+//  instructions-per-frame 10
+//  vblank-wait off
+//  backend interpreter
+//  hash-interval 60
+//  frames
+//  -
+//  5
+//  5 0f3c9a81d2e47b66
+
+const MAGIC: &str = "chip8-movie 2";
+const OLD_MAGIC: &str = "chip8-movie 1";
+
+//how often a state hash is stored, once a second by default
+pub const HASH_INTERVAL: u64 = 60;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MovieHeader {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub quirks: Quirks,
+    pub backend: Backend,
+    pub hash_interval: u64,
+}
+
+//the header lines for the settings, in the order they are written
+fn settings(quirks: &Quirks, backend: Backend) -> Vec<(&'static str, String)> {
+    vec![
+        ("font", quirks.font.name().to_string()),
+        ("font-address", format!("{:03x}", quirks.font_address)),
+        ("timing", quirks.timing.name().to_string()),
+        ("instructions-per-frame", quirks.instructions_per_frame.to_string()),
+        ("vblank-wait", if quirks.vblank_wait { "on" } else { "off" }.to_string()),
+        ("backend", backend.name().to_string()),
+    ]
+}
+
+impl MovieHeader {
+    //names the first setting a replay with these would differ from the recording in
+    pub fn check_settings(&self, quirks: &Quirks, backend: Backend) -> Result<(), String> {
+        let recorded = settings(&self.quirks, self.backend);
+        for ((name, then), (_, now)) in recorded.into_iter().zip(settings(quirks, backend)) {
+            if then != now {
+                return Err(format!("was recorded with {} {}, not {}", name, then, now));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct MovieFrame {
+    key: u8,
+    hash: Option<u64>,
+}
+
+pub struct MovieWriter {
+    out: BufWriter<File>,
+    hash_interval: u64,
+}
+
+impl MovieWriter {
+    pub fn create(path: &Path, header: &MovieHeader) -> Result<MovieWriter, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+        let mut out = BufWriter::new(file);
+
+        let mut text = format!("{}\nrom {:016x}\nseed {}\n", MAGIC, header.rom_hash, header.seed);
+        for (name, value) in settings(&header.quirks, header.backend) {
+            text += &format!("{} {}\n", name, value);
+        }
+        text += &format!("hash-interval {}\nframes", header.hash_interval);
+        writeln!(out, "{}", text).map_err(|e| e.to_string())?;
+
+        Ok(MovieWriter { out, hash_interval: header.hash_interval })
+    }
+
+    //call after the frame has run, with the key that was used for it
+    pub fn record_frame(&mut self, key: u8, machine: &Machine) -> Result<(), String> {
+        let result = if key == 0xff {
+            write!(self.out, "-")
+        } else {
+            write!(self.out, "{:x}", key)
+        };
+        result.map_err(|e| e.to_string())?;
+
+        if machine.frame.is_multiple_of(self.hash_interval) {
+            write!(self.out, " {:016x}", machine.state_hash()).map_err(|e| e.to_string())?;
+        }
+
+        writeln!(self.out).map_err(|e| e.to_string())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        self.out.flush().map_err(|e| e.to_string())
+    }
+}
+
+pub struct MoviePlayer {
+    pub header: MovieHeader,
+    frames: Vec<MovieFrame>,
+    position: usize,
+}
+
+fn parse_hex(text: &str, line: usize) -> Result<u64, String> {
+    u64::from_str_radix(text, 16).map_err(|_| format!("movie line {}: bad hex '{}'", line, text))
+}
+
+impl MoviePlayer {
+    pub fn load(path: &Path) -> Result<MoviePlayer, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("could not read movie {}: {}", path.display(), e))?;
+        let mut lines = contents.lines().enumerate();
+
+        match lines.next().map(|(_, l)| l) {
+            Some(MAGIC) => {},
+            Some(OLD_MAGIC) => {
+                return Err(format!("{} is from an older version that didn't record the settings, record it again",
+                    path.display()));
+            },
+            _ => return Err(format!("{} is not a chip8 movie", path.display())),
+        }
+
+        let mut rom_hash = None;
+        let mut seed = None;
+        let mut font = None;
+        let mut font_address = None;
+        let mut timing = None;
+        let mut instructions_per_frame = None;
+        let mut vblank_wait = None;
+        let mut backend = None;
+        let mut hash_interval = None;
+
+        for (num, line) in &mut lines {
+            if line == "frames" {
+                break;
+            }
+
+            let (key, value) = line.split_once(' ')
+                .ok_or(format!("movie line {}: expected key value", num + 1))?;
+            let bad_number = |_| format!("movie line {}: bad number '{}'", num + 1, value);
+            let at_line = |e| format!("movie line {}: {}", num + 1, e);
+
+            match key {
+                "rom" => rom_hash = Some(parse_hex(value, num + 1)?),
+                "seed" => seed = Some(value.parse().map_err(bad_number)?),
+                "font" => {
+                    font = Some(FontSet::from_name(value)
+                        .ok_or(format!("movie line {}: unknown font set '{}'", num + 1, value))?);
+                },
+                "font-address" => {
+                    let address = u16::try_from(parse_hex(value, num + 1)?)
+                        .map_err(|_| format!("movie line {}: bad font address '{}'", num + 1, value))?;
+                    machine::check_font_address(address).map_err(at_line)?;
+                    font_address = Some(address);
+                },
+                "timing" => timing = Some(Timing::parse(value).map_err(at_line)?),
+                "instructions-per-frame" => {
+                    let ipf = value.parse().map_err(bad_number)?;
+                    if ipf == 0 {
+                        return Err(at_line("instructions per frame must be at least 1".to_string()));
+                    }
+                    instructions_per_frame = Some(ipf);
+                },
+                "vblank-wait" => vblank_wait = Some(parse_bool(value).map_err(at_line)?),
+                "backend" => backend = Some(Backend::parse(value).map_err(at_line)?),
+                "hash-interval" => hash_interval = Some(value.parse().map_err(bad_number)?),
+                _ => return Err(format!("movie line {}: unknown key '{}'", num + 1, key)),
+            }
+        }
+
+        let missing = |name: &str| format!("movie is missing '{}'", name);
+        let header = MovieHeader {
+            rom_hash: rom_hash.ok_or(missing("rom"))?,
+            seed: seed.ok_or(missing("seed"))?,
+            quirks: Quirks {
+                font: font.ok_or(missing("font"))?,
+                font_address: font_address.ok_or(missing("font-address"))?,
+                timing: timing.ok_or(missing("timing"))?,
+                instructions_per_frame: instructions_per_frame.ok_or(missing("instructions-per-frame"))?,
+                vblank_wait: vblank_wait.ok_or(missing("vblank-wait"))?,
+            },
+            backend: backend.ok_or(missing("backend"))?,
+            hash_interval: hash_interval.ok_or(missing("hash-interval"))?,
+        };
+
+        let mut frames = Vec::new();
+        for (num, line) in lines {
+            let mut fields = line.split_whitespace();
+
+            let key = match fields.next() {
+                Some("-") => 0xff,
+                Some(key) => parse_hex(key, num + 1)
+                    .ok().filter(|key| *key < 16)
+                    .ok_or(format!("movie line {}: bad key '{}'", num + 1, key))? as u8,
+                None => continue,
+            };
+            let hash = match fields.next() {
+                Some(hash) => Some(parse_hex(hash, num + 1)?),
+                None => None,
+            };
+
+            frames.push(MovieFrame { key, hash });
+        }
+
+        Ok(MoviePlayer { header, frames, position: 0 })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.position >= self.frames.len()
+    }
+
+    //key for the next frame, None once the movie has run out
+    pub fn next_key(&self) -> Option<u8> {
+        self.frames.get(self.position).map(|f| f.key)
+    }
+
+    //call after the frame has run to compare against the recorded state
+    pub fn check_frame(&mut self, machine: &Machine) -> Result<(), String> {
+        let frame = &self.frames[self.position];
+        self.position += 1;
+
+        match frame.hash {
+            Some(expected) if expected != machine.state_hash() => {
+                Err(format!("replay desynced at frame {}: expected state {:016x}, got {:016x}",
+                    machine.frame, expected, machine.state_hash()))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "chip8-movie 2
+rom 8c2d6e1f0a9b3c47
+seed 1234
+font schip
+font-address 000
+timing fixed
+instructions-per-frame 10
+vblank-wait off
+backend interpreter
+hash-interval 60
+frames
+-
+";
+
+    fn load_with(from: &str, to: &str) -> Result<MoviePlayer, String> {
+        let path = std::env::temp_dir().join(format!("chip8-movie-test-{}-{}", std::process::id(), to.len()));
+        fs::write(&path, HEADER.replace(from, to)).unwrap();
+        let result = MoviePlayer::load(&path);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn loads_the_documented_header() {
+        assert!(load_with("", "").is_ok());
+    }
+
+    #[test]
+    fn rejects_settings_a_machine_can_not_run_with() {
+        let error = load_with("font-address 000", "font-address 1c0").err().unwrap();
+        assert!(error.contains("overlap"), "{}", error);
+        assert!(load_with("font-address 000", "font-address 10000").is_err());
+
+        let error = load_with("instructions-per-frame 10", "instructions-per-frame 0").err().unwrap();
+        assert!(error.contains("at least 1"), "{}", error);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::display::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
+
+const WIDTH: usize = DISPLAY_WIDTH as usize;
+const HEIGHT: usize = DISPLAY_HEIGHT as usize;
+
+//per pixel brightness for one rendered frame, 0.0 is background and 1.0 is fully lit
+pub type Intensity = [[f32; HEIGHT]; WIDTH];
+
+//Games erase and redraw sprites with XOR, so a sprite is often missing for a
+//frame or two. Both modes keep recently lit pixels visible to hide that.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PhosphorMode {
+    #[default]
+    Off,
+    //each frame keeps `decay` of the previous frame's brightness
+    Blend { decay: f32 },
+    //a pixel stays lit while it was set in any of the last `frames` frames
+    Or { frames: usize },
+}
+
+impl PhosphorMode {
+    //off, low, medium, high, blend:<0.0-1.0> or or:<frames>
+    pub fn parse(text: &str) -> Result<PhosphorMode, String> {
+        let mode = match text {
+            "off" => PhosphorMode::Off,
+            "low" => PhosphorMode::Blend { decay: 0.5 },
+            "medium" => PhosphorMode::Blend { decay: 0.7 },
+            "high" => PhosphorMode::Blend { decay: 0.85 },
+            _ => {
+                let (kind, strength) = text.split_once(':')
+                    .ok_or(format!("unknown phosphor mode '{}'", text))?;
+
+                match kind {
+                    "blend" => {
+                        let decay: f32 = strength.parse()
+                            .map_err(|_| format!("bad blend strength '{}'", strength))?;
+                        if !(0.0..1.0).contains(&decay) {
+                            return Err("blend strength must be between 0.0 and 1.0".to_string());
+                        }
+                        PhosphorMode::Blend { decay }
+                    },
+                    "or" => {
+                        let frames: usize = strength.parse()
+                            .map_err(|_| format!("bad frame count '{}'", strength))?;
+                        if frames == 0 {
+                            return Err("or needs at least 1 frame".to_string());
+                        }
+                        PhosphorMode::Or { frames }
+                    },
+                    _ => return Err(format!("unknown phosphor mode '{}'", text)),
+                }
+            },
+        };
+
+        Ok(mode)
+    }
+}
+
+pub struct Phosphor {
+    mode: PhosphorMode,
+    intensity: Intensity,
+    //ring of previous frames for PhosphorMode::Or
+    history: Vec<[[u8; HEIGHT]; WIDTH]>,
+    next: usize,
+}
+
+impl Phosphor {
+    pub fn new(mode: PhosphorMode) -> Phosphor {
+        let frames = match mode {
+            PhosphorMode::Or { frames } => frames,
+            _ => 0,
+        };
+
+        Phosphor {
+            mode,
+            intensity: [[0.0; HEIGHT]; WIDTH],
+            history: vec![[[0; HEIGHT]; WIDTH]; frames],
+            next: 0,
+        }
+    }
+
+    //feed the current framebuffer in once per 60 Hz frame
+    pub fn update(&mut self, display_mem: &[[u8; 64]; 128]) -> &Intensity {
+        match self.mode {
+            PhosphorMode::Off => {
+                for (column, pixels) in self.intensity.iter_mut().zip(display_mem) {
+                    for (amount, pixel) in column.iter_mut().zip(pixels) {
+                        *amount = *pixel as f32;
+                    }
+                }
+            },
+            PhosphorMode::Blend { decay } => {
+                for (column, pixels) in self.intensity.iter_mut().zip(display_mem) {
+                    for (amount, pixel) in column.iter_mut().zip(pixels) {
+                        *amount = (*amount * decay).max(*pixel as f32);
+                    }
+                }
+            },
+            PhosphorMode::Or { .. } => {
+                let slot = &mut self.history[self.next];
+                for x in 0..WIDTH {
+                    slot[x].copy_from_slice(&display_mem[x][..HEIGHT]);
+                }
+                self.next = (self.next + 1) % self.history.len();
+
+                for x in 0..WIDTH {
+                    for y in 0..HEIGHT {
+                        let lit = self.history.iter().any(|frame| frame[x][y] != 0);
+                        self.intensity[x][y] = if lit { 1.0 } else { 0.0 };
+                    }
+                }
+            },
+        }
+
+        &self.intensity
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::disasm;
+use crate::machine::Machine;
+
+//Counts executions per address and attributes every instruction to the chain
+//of subroutines it ran under, tracked from 2nnn calls and 00EE returns. The
+//chains are written in the folded stack format flamegraph.pl and inferno read.
+
+//rows in each table of the report
+const REPORT_ROWS: usize = 20;
+
+#[derive(Clone, Debug, Default)]
+pub struct ProfileConfig {
+    //sorted hot spot report
+    pub report: Option<PathBuf>,
+    //one "main;sub_2a0;sub_2f4 count" line per call chain
+    pub folded: Option<PathBuf>,
+}
+
+impl ProfileConfig {
+    pub fn enabled(&self) -> bool {
+        self.report.is_some() || self.folded.is_some()
+    }
+}
+
+fn frame_name(entry: u16) -> String {
+    if entry == 0x200 {
+        "main".to_string()
+    } else {
+        format!("sub_{:03x}", entry)
+    }
+}
+
+pub struct Profiler {
+    config: ProfileConfig,
+    executions: Vec<u64>,
+    //entry addresses of the subroutines being run, outermost first
+    stack: Vec<u16>,
+    stacks: HashMap<Vec<u16>, u64>,
+    total: u64,
+}
+
+impl Profiler {
+    pub fn new(config: &ProfileConfig) -> Profiler {
+        Profiler {
+            config: config.clone(),
+            executions: vec![0; 4096],
+            stack: vec![0x200],
+            stacks: HashMap::new(),
+            total: 0,
+        }
+    }
+
+    //call before each instruction runs
+    pub fn record(&mut self, machine: &Machine) {
+        let pc = machine.reg.PC;
+        let instruction = machine.fetch(pc);
+
+        self.executions[pc as usize % 4096] += 1;
+        self.total += 1;
+        match self.stacks.get_mut(&self.stack) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.stack.clone(), 1);
+            },
+        }
+
+        //the call and return themselves count towards the caller
+        if instruction >> 12 == 0x2 {
+            self.stack.push(instruction & 0x0fff);
+        } else if instruction == 0x00ee && self.stack.len() > 1 {
+            self.stack.pop();
+        }
+    }
+
+    fn folded(&self) -> String {
+        let mut lines: Vec<String> = self.stacks.iter()
+            .map(|(stack, count)| {
+                let names: Vec<String> = stack.iter().map(|entry| frame_name(*entry)).collect();
+                format!("{} {}", names.join(";"), count)
+            })
+            .collect();
+        lines.sort();
+
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    fn report(&self, machine: &Machine) -> String {
+        let percent = |count: u64| 100.0 * count as f64 / self.total.max(1) as f64;
+        let mut out = String::new();
+
+        writeln!(out, "{} instructions", self.total).unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "hottest addresses").unwrap();
+        writeln!(out, "{:>5} {:>12} {:>7}  instruction", "addr", "count", "%").unwrap();
+
+        let mut addresses: Vec<usize> = (0..4096).filter(|addr| self.executions[*addr] > 0).collect();
+        addresses.sort_by_key(|addr| (std::cmp::Reverse(self.executions[*addr]), *addr));
+
+        for addr in addresses.iter().take(REPORT_ROWS) {
+            let count = self.executions[*addr];
+            writeln!(out, "{:>5} {:>12} {:>6.2}%  {}", format!("{:03x}", addr), count,
+                percent(count), disasm::mnemonic(machine.fetch(*addr as u16))).unwrap();
+        }
+
+        //self is time spent in the subroutine's own instructions, total includes
+        //everything it called
+        let mut subroutines: HashMap<u16, (u64, u64)> = HashMap::new();
+        for (stack, count) in &self.stacks {
+            let innermost = *stack.last().unwrap();
+            subroutines.entry(innermost).or_insert((0, 0)).0 += count;
+
+            let mut seen = Vec::new();
+            for entry in stack {
+                //recursion shouldn't count the same cycles twice
+                if !seen.contains(entry) {
+                    seen.push(*entry);
+                    subroutines.entry(*entry).or_insert((0, 0)).1 += count;
+                }
+            }
+        }
+
+        let mut subroutines: Vec<(u16, (u64, u64))> = subroutines.into_iter().collect();
+        subroutines.sort_by_key(|(entry, (own, _))| (std::cmp::Reverse(*own), *entry));
+
+        writeln!(out).unwrap();
+        writeln!(out, "subroutines").unwrap();
+        writeln!(out, "{:>12} {:>7} {:>12} {:>7}  name", "self", "%", "total", "%").unwrap();
+
+        for (entry, (own, total)) in subroutines.iter().take(REPORT_ROWS) {
+            writeln!(out, "{:>12} {:>6.2}% {:>12} {:>6.2}%  {}",
+                own, percent(*own), total, percent(*total), frame_name(*entry)).unwrap();
+        }
+
+        out
+    }
+
+    //writes whichever outputs were asked for
+    pub fn finish(&self, machine: &Machine) -> Result<(), String> {
+        if let Some(path) = &self.config.report {
+            fs::write(path, self.report(machine))
+                .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        }
+
+        if let Some(path) = &self.config.folded {
+            fs::write(path, self.folded())
+                .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        }
+
+        Ok(())
+    }
+}
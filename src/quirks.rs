@@ -0,0 +1,172 @@
+use crate::timing::Timing;
+
+//Behaviour that differs between the CHIP-8 implementations ROMs were written
+//for, grouped into named profiles so a ROM can be run the way its author saw it.
+
+//bytes in a font set, sixteen 4x5 hex digits
+pub const FONT_SIZE: usize = 80;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontSet {
+    //the digits from the COSMAC VIP interpreter ROM
+    Vip,
+    //the narrow digits of the HP48 CHIP-48
+    Chip48,
+    //SCHIP 1.1's small font, the one most emulators and docs use
+    Schip,
+    //Octo's small font, SCHIP's apart from a narrower 4 and squared off B and D
+    Octo,
+}
+
+impl FontSet {
+    pub fn from_name(name: &str) -> Option<FontSet> {
+        match name {
+            "vip" => Some(FontSet::Vip),
+            "chip48" => Some(FontSet::Chip48),
+            "schip" => Some(FontSet::Schip),
+            "octo" => Some(FontSet::Octo),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FontSet::Vip => "vip",
+            FontSet::Chip48 => "chip48",
+            FontSet::Schip => "schip",
+            FontSet::Octo => "octo",
+        }
+    }
+
+    pub fn glyphs(&self) -> [u8; FONT_SIZE] {
+        match self {
+            FontSet::Vip => [
+                0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+                0x60, 0x20, 0x20, 0x20, 0x70, // 1
+                0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+                0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+                0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+                0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+                0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+                0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+                0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+                0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+                0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+                0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+                0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+                0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+                0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+                0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+            ],
+            FontSet::Chip48 => [
+                0x60, 0xA0, 0xA0, 0xA0, 0xC0, // 0
+                0x40, 0xC0, 0x40, 0x40, 0xE0, // 1
+                0xC0, 0x20, 0x40, 0x80, 0xE0, // 2
+                0xC0, 0x20, 0x40, 0x20, 0xC0, // 3
+                0x20, 0xA0, 0xE0, 0x20, 0x20, // 4
+                0xE0, 0x80, 0xC0, 0x20, 0xC0, // 5
+                0x40, 0x80, 0xC0, 0xA0, 0x40, // 6
+                0xE0, 0x20, 0x60, 0x40, 0x40, // 7
+                0x40, 0xA0, 0x40, 0xA0, 0x40, // 8
+                0x40, 0xA0, 0x60, 0x20, 0x40, // 9
+                0x40, 0xA0, 0xE0, 0xA0, 0xA0, // A
+                0xC0, 0xA0, 0xC0, 0xA0, 0xC0, // B
+                0x60, 0x80, 0x80, 0x80, 0x60, // C
+                0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+                0xE0, 0x80, 0xC0, 0x80, 0xE0, // E
+                0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+            ],
+            FontSet::Schip => [
+                0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+                0x20, 0x60, 0x20, 0x20, 0x70, // 1
+                0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+                0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+                0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+                0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+                0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+                0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+                0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+                0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+                0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+                0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+                0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+                0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+                0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+                0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+            ],
+            FontSet::Octo => [
+                0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+                0x20, 0x60, 0x20, 0x20, 0x70, // 1
+                0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+                0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+                0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+                0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+                0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+                0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+                0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+                0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+                0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+                0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+                0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+                0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+                0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+                0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quirks {
+    pub font: FontSet,
+    //where the font is loaded, Fx29 points I into it
+    pub font_address: u16,
+    pub timing: Timing,
+    //how many instructions a frame is with fixed timing
+    pub instructions_per_frame: usize,
+    //Dxyn waits for the next display interrupt before drawing like the VIP
+    //did, so at most one sprite is drawn each frame
+    pub vblank_wait: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::from_profile("schip").unwrap()
+    }
+}
+
+impl Quirks {
+    pub fn from_profile(name: &str) -> Option<Quirks> {
+        match name {
+            "vip" => Some(Quirks {
+                font: FontSet::Vip,
+                font_address: 0x000,
+                instructions_per_frame: 11,
+                timing: Timing::Vip,
+                vblank_wait: true,
+            }),
+            "chip48" => Some(Quirks {
+                font: FontSet::Chip48,
+                font_address: 0x000,
+                instructions_per_frame: 30,
+                timing: Timing::Fixed,
+                vblank_wait: false,
+            }),
+            "schip" => Some(Quirks {
+                font: FontSet::Schip,
+                font_address: 0x000,
+                instructions_per_frame: 30,
+                timing: Timing::Fixed,
+                vblank_wait: false,
+            }),
+            "octo" => Some(Quirks {
+                font: FontSet::Octo,
+                font_address: 0x000,
+                instructions_per_frame: 1000,
+                timing: Timing::Fixed,
+                vblank_wait: false,
+            }),
+            _ => None,
+        }
+    }
+}
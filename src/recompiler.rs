@@ -0,0 +1,273 @@
+use rand::Rng;
+
+use crate::decode::Decoded;
+use crate::machine::Machine;
+
+//Runs straight-line code a basic block at a time. Each block is translated
+//once into closures with the instruction's fields already bound, so running
+//it is a walk down a Vec with no fetch or decode. Jumps and skips that only
+//look at registers end a block as a closure too, anything else that branches,
+//waits, draws or writes memory ends it and is handed to the interpreter, so
+//those only have to be right in one place. A block keeps the bytes it was
+//made from and is rebuilt when they change, and addresses that keep changing
+//are left to the interpreter for good.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backend {
+    #[default]
+    Interpreter,
+    Recompiler,
+}
+
+impl Backend {
+    pub fn parse(text: &str) -> Result<Backend, String> {
+        match text {
+            "interpreter" => Ok(Backend::Interpreter),
+            "recompiler" => Ok(Backend::Recompiler),
+            _ => Err(format!("unknown backend '{}'", text)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Interpreter => "interpreter",
+            Backend::Recompiler => "recompiler",
+        }
+    }
+}
+
+//longest run of straight-line instructions in one block
+const MAX_BLOCK_LEN: usize = 64;
+
+//rebuilds of one block before its address is only interpreted
+const MAX_RECOMPILES: u8 = 8;
+
+type Op = Box<dyn Fn(&mut Machine) + Send>;
+
+enum Exit {
+    //the block was cut off at its longest and carries on into the next one
+    FallThrough,
+    //a closure that sets PC itself
+    Branch(Op),
+    Interpreter,
+}
+
+struct Block {
+    //the code the block was made from, the last instruction included
+    bytes: Vec<u8>,
+    //decode cache generation the bytes were last checked at
+    generation: u64,
+    ops: Vec<Op>,
+    exit: Exit,
+}
+
+//straight-line instructions as closures, None for the ones the interpreter runs
+fn translate(decoded: Decoded) -> Option<Op> {
+    let x = decoded.x as usize;
+    let y = decoded.y as usize;
+    let kk = decoded.kk;
+    let nnn = decoded.nnn;
+
+    let op: Op = match (decoded.opcode, kk) {
+        (0x0, 0xe0) => Box::new(|m: &mut Machine| m.display_mem = [[0u8; 64]; 128]),
+        (0x0, 0xee) => return None,
+        //machine code subroutines aren't run
+        (0x0, _) => Box::new(|_: &mut Machine| {}),
+        (0x6, _) => Box::new(move |m: &mut Machine| m.reg.V[x] = kk),
+        (0x7, _) => Box::new(move |m: &mut Machine| m.reg.V[x] = m.reg.V[x].wrapping_add(kk)),
+        (0x8, _) => match decoded.n {
+            0x0 => Box::new(move |m: &mut Machine| m.reg.V[x] = m.reg.V[y]),
+            0x1 => Box::new(move |m: &mut Machine| m.reg.V[x] |= m.reg.V[y]),
+            0x2 => Box::new(move |m: &mut Machine| m.reg.V[x] &= m.reg.V[y]),
+            0x3 => Box::new(move |m: &mut Machine| m.reg.V[x] ^= m.reg.V[y]),
+            0x4 => Box::new(move |m: &mut Machine| {
+                let (sum, carry) = m.reg.V[x].overflowing_add(m.reg.V[y]);
+                m.reg.V[x] = sum;
+                m.reg.V[0xf] = carry as u8;
+            }),
+            0x5 => Box::new(move |m: &mut Machine| {
+                let (v_x, v_y) = (m.reg.V[x], m.reg.V[y]);
+                m.reg.V[x] = v_x.wrapping_sub(v_y);
+                m.reg.V[0xf] = (v_x >= v_y) as u8;
+            }),
+            0x6 => Box::new(move |m: &mut Machine| {
+                let v_x = m.reg.V[x];
+                m.reg.V[x] = v_x / 2;
+                m.reg.V[0xf] = v_x & 1;
+            }),
+            0x7 => Box::new(move |m: &mut Machine| {
+                let (v_x, v_y) = (m.reg.V[x], m.reg.V[y]);
+                m.reg.V[x] = v_y.wrapping_sub(v_x);
+                m.reg.V[0xf] = (v_y >= v_x) as u8;
+            }),
+            0xe => Box::new(move |m: &mut Machine| {
+                let v_x = m.reg.V[x];
+                m.reg.V[x] = v_x.wrapping_mul(2);
+                m.reg.V[0xf] = v_x >> 7;
+            }),
+            _ => return None,
+        },
+        (0xa, _) => Box::new(move |m: &mut Machine| m.reg.I = nnn),
+        (0xc, _) => Box::new(move |m: &mut Machine| {
+            let rnd: u8 = m.rng.gen();
+            m.reg.V[x] = rnd & kk;
+        }),
+        (0xf, 0x07) => Box::new(move |m: &mut Machine| m.reg.V[x] = m.reg.DT),
+        (0xf, 0x15) => Box::new(move |m: &mut Machine| m.reg.DT = m.reg.V[x]),
+        (0xf, 0x18) => Box::new(move |m: &mut Machine| m.reg.ST = m.reg.V[x]),
+        (0xf, 0x1e) => Box::new(move |m: &mut Machine| m.reg.I = m.reg.I.wrapping_add(m.reg.V[x] as u16)),
+        (0xf, 0x29) => Box::new(move |m: &mut Machine| {
+            m.reg.I = m.font_address + (m.reg.V[x] & 0x0f) as u16 * 5;
+        }),
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+//block ending instructions that only read registers, addr is where they are
+fn translate_branch(decoded: Decoded, addr: u16) -> Option<Op> {
+    let x = decoded.x as usize;
+    let kk = decoded.kk;
+    let nnn = decoded.nnn;
+    let (next, skip) = (addr + 2, addr + 4);
+
+    let op: Op = match (decoded.opcode, kk) {
+        (0x1, _) => Box::new(move |m: &mut Machine| m.reg.PC = nnn),
+        (0x3, _) => Box::new(move |m: &mut Machine| {
+            m.reg.PC = if m.reg.V[x] == kk { skip } else { next };
+        }),
+        (0x4, _) => Box::new(move |m: &mut Machine| {
+            m.reg.PC = if m.reg.V[x] != kk { skip } else { next };
+        }),
+        (0xe, 0x9e) => Box::new(move |m: &mut Machine| {
+            m.reg.PC = if m.key_down(m.reg.V[x]) { skip } else { next };
+        }),
+        (0xe, 0xa1) => Box::new(move |m: &mut Machine| {
+            m.reg.PC = if !m.key_down(m.reg.V[x]) { skip } else { next };
+        }),
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+fn compile(machine: &Machine, start: u16) -> Block {
+    let mut ops = Vec::new();
+    let mut addr = start;
+    let mut exit = Exit::FallThrough;
+
+    //blocks stop short of the end of memory rather than wrapping
+    while ops.len() < MAX_BLOCK_LEN && addr < 0xfff {
+        let decoded = Decoded::new(machine.fetch(addr));
+        if let Some(op) = translate(decoded) {
+            ops.push(op);
+            addr += 2;
+            continue;
+        }
+
+        exit = match translate_branch(decoded, addr) {
+            Some(op) => Exit::Branch(op),
+            None => Exit::Interpreter,
+        };
+        addr += 2;
+        break;
+    }
+
+    Block {
+        bytes: machine.memory[start as usize..addr as usize].to_vec(),
+        generation: machine.decode_cache.generation(),
+        ops,
+        exit,
+    }
+}
+
+pub struct Recompiler {
+    blocks: Vec<Option<Block>>,
+    recompiles: Vec<u8>,
+}
+
+impl Default for Recompiler {
+    fn default() -> Recompiler {
+        Recompiler::new()
+    }
+}
+
+impl Recompiler {
+    pub fn new() -> Recompiler {
+        Recompiler {
+            blocks: (0..4096).map(|_| None).collect(),
+            recompiles: vec![0; 4096],
+        }
+    }
+
+    //runs blocks until limit instructions have run. True when it stopped
+    //early on a Dxyn that is waiting for vblank, which ends the frame, Err
+    //when the interpreter couldn't run an instruction
+    pub fn run(&mut self, machine: &mut Machine, limit: u64) -> Result<bool, String> {
+        let end = machine.cycle + limit;
+
+        while machine.cycle < end {
+            if self.run_block(machine, end - machine.cycle)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    //the block at PC, or the first limit instructions of it
+    fn run_block(&mut self, machine: &mut Machine, limit: u64) -> Result<bool, String> {
+        let pc = machine.reg.PC;
+        let index = pc as usize % 4096;
+
+        //memory is only compared when something has been written since the last check
+        let generation = machine.decode_cache.generation();
+        let stale = match &mut self.blocks[index] {
+            Some(block) if block.generation == generation => false,
+            Some(block) => {
+                block.generation = generation;
+                machine.memory[index..index + block.bytes.len()] != block.bytes[..]
+            },
+            None => true,
+        };
+        if stale {
+            if self.recompiles[index] >= MAX_RECOMPILES || pc >= 0xfff {
+                return interpret(machine);
+            }
+            if self.blocks[index].is_some() {
+                self.recompiles[index] += 1;
+            }
+            self.blocks[index] = Some(compile(machine, pc));
+        }
+
+        let block = self.blocks[index].as_ref().unwrap();
+        let count = block.ops.len().min(limit as usize);
+        for op in &block.ops[..count] {
+            op(machine);
+        }
+        machine.reg.PC = pc + 2 * count as u16;
+        machine.cycle += count as u64;
+
+        if count < block.ops.len() || count as u64 == limit {
+            return Ok(false);
+        }
+
+        match &block.exit {
+            Exit::FallThrough => Ok(false),
+            Exit::Branch(op) => {
+                op(machine);
+                machine.cycle += 1;
+                Ok(false)
+            },
+            Exit::Interpreter => interpret(machine),
+        }
+    }
+}
+
+fn interpret(machine: &mut Machine) -> Result<bool, String> {
+    let pc = machine.reg.PC;
+    let instruction = machine.fetch(pc);
+    machine.step()?;
+
+    Ok(instruction >> 12 == 0xd && machine.reg.PC == pc)
+}
@@ -0,0 +1,140 @@
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::display::{Palette, DISPLAY_WIDTH, DISPLAY_HEIGHT};
+use crate::screenshot;
+
+//a frame that hasn't been written yet because the following frames may be identical
+struct PendingFrame {
+    pixels: Vec<u8>,
+    first_frame: u64,
+}
+
+enum Output {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        pending: Option<PendingFrame>,
+    },
+    //every frame as dir/frame-NNNNNN.png, for feeding to a video encoder
+    PngSequence {
+        dir: PathBuf,
+    },
+}
+
+pub struct Recorder {
+    output: Output,
+    palette: Palette,
+    scale: u32,
+    frames: u64,
+    pub path: PathBuf,
+}
+
+//GIF delays are in hundredths of a second, frames are 1/60 s
+fn centiseconds(frames: u64) -> u64 {
+    (frames * 100 + 30) / 60
+}
+
+//GIF sizes are 16 bit, so a big enough scale doesn't fit
+fn gif_size(scale: u32) -> Result<(u16, u16), String> {
+    let side = |pixels: u32| pixels.checked_mul(scale).and_then(|size| u16::try_from(size).ok());
+    match (side(DISPLAY_WIDTH), side(DISPLAY_HEIGHT)) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(format!("scale {} is too big to record a GIF at, the most is {}",
+            scale, u16::MAX as u32 / DISPLAY_WIDTH)),
+    }
+}
+
+impl Recorder {
+    //a path ending in .gif records an animated GIF, anything else is a directory of PNGs
+    pub fn start(path: &Path, palette: &Palette, scale: u32) -> Result<Recorder, String> {
+        let is_gif = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+
+        let output = if is_gif {
+            let (width, height) = gif_size(scale)?;
+            let file = File::create(path)
+                .map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+
+            let mut colors = Vec::new();
+            for color in &palette.colors[..2] {
+                colors.extend_from_slice(&[color.r, color.g, color.b]);
+            }
+
+            let mut encoder = gif::Encoder::new(BufWriter::new(file), width, height, &colors)
+                .map_err(|e| e.to_string())?;
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+
+            Output::Gif { encoder, pending: None }
+        } else {
+            fs::create_dir_all(path)
+                .map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+
+            Output::PngSequence { dir: path.to_path_buf() }
+        };
+
+        Ok(Recorder {
+            output,
+            palette: *palette,
+            scale,
+            frames: 0,
+            path: path.to_path_buf(),
+        })
+    }
+
+    //call once per 60 Hz frame
+    pub fn capture(&mut self, display_mem: &[[u8; 64]; 128]) -> Result<(), String> {
+        match &mut self.output {
+            Output::Gif { encoder, pending } => {
+                let pixels = screenshot::indexed_frame(display_mem, self.scale);
+
+                //identical frames just extend the delay of the one before
+                let changed = pending.as_ref().is_none_or(|p| p.pixels != pixels);
+                if changed {
+                    if let Some(previous) = pending.take() {
+                        write_gif_frame(encoder, previous, self.frames, self.scale)?;
+                    }
+                    *pending = Some(PendingFrame { pixels, first_frame: self.frames });
+                }
+            },
+            Output::PngSequence { dir } => {
+                let path = dir.join(format!("frame-{:06}.png", self.frames));
+                screenshot::save_png(&path, display_mem, &self.palette, self.scale)?;
+            },
+        }
+
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        if let Output::Gif { mut encoder, pending } = self.output {
+            if let Some(last) = pending {
+                write_gif_frame(&mut encoder, last, self.frames, self.scale)?;
+            }
+            encoder.into_inner().map_err(|e| e.to_string())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_gif_frame(encoder: &mut gif::Encoder<BufWriter<File>>, frame: PendingFrame,
+    end_frame: u64, scale: u32) -> Result<(), String> {
+
+    //work from absolute times so rounding doesn't drift over a long recording
+    let delay = centiseconds(end_frame) - centiseconds(frame.first_frame);
+    let (width, height) = gif_size(scale)?;
+
+    let gif_frame = gif::Frame {
+        width,
+        height,
+        delay: delay.min(u16::MAX as u64) as u16,
+        buffer: Cow::Owned(frame.pixels),
+        ..gif::Frame::default()
+    };
+
+    encoder.write_frame(&gif_frame).map_err(|e| e.to_string())
+}
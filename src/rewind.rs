@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+
+//Consecutive frames differ in only a few bytes, so history is kept as the
+//newest full state plus, for every older frame, the XOR against the frame
+//after it, run length encoded. Rewinding walks back from the newest state and
+//the oldest entry can be dropped at any time because nothing depends on it.
+
+#[derive(Clone, Copy, Debug)]
+pub struct RewindConfig {
+    //frames of history to keep, 0 turns rewind off
+    pub frames: usize,
+    //upper bound for the compressed history in bytes
+    pub memory_budget: usize,
+}
+
+impl Default for RewindConfig {
+    fn default() -> RewindConfig {
+        RewindConfig {
+            frames: 600,
+            memory_budget: 8 * 1024 * 1024,
+        }
+    }
+}
+
+pub struct RewindBuffer {
+    config: RewindConfig,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    bytes: usize,
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+//XOR of two states as (zero run, literal count, literals) triples
+fn encode_delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let diff: Vec<u8> = older.iter().zip(newer).map(|(a, b)| a ^ b).collect();
+    let mut out = Vec::new();
+    let mut pos = 0;
+
+    while pos < diff.len() {
+        let zeros = diff[pos..].iter().take_while(|&&b| b == 0).count();
+        pos += zeros;
+
+        let literals = diff[pos..].iter().take_while(|&&b| b != 0).count();
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals);
+        out.extend_from_slice(&diff[pos..pos + literals]);
+        pos += literals;
+    }
+
+    out
+}
+
+//turns `newer` back into the older state in place
+fn apply_delta(newer: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut out = 0;
+
+    while pos < delta.len() {
+        out += read_varint(delta, &mut pos);
+        let literals = read_varint(delta, &mut pos);
+        for byte in &delta[pos..pos + literals] {
+            newer[out] ^= byte;
+            out += 1;
+        }
+        pos += literals;
+    }
+}
+
+impl RewindBuffer {
+    pub fn new(config: RewindConfig) -> RewindBuffer {
+        RewindBuffer {
+            config,
+            latest: None,
+            deltas: VecDeque::new(),
+            bytes: 0,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.frames > 0
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if !self.enabled() {
+            return;
+        }
+
+        if let Some(latest) = self.latest.take() {
+            let delta = encode_delta(&latest, &state);
+            self.bytes += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(state);
+
+        while self.deltas.len() > self.config.frames || self.bytes > self.config.memory_budget {
+            match self.deltas.pop_front() {
+                Some(oldest) => self.bytes -= oldest.len(),
+                None => break,
+            }
+        }
+    }
+
+    //the state one frame before the newest, which then becomes the newest
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        self.bytes -= delta.len();
+
+        let latest = self.latest.as_mut()?;
+        apply_delta(latest, &delta);
+
+        Some(latest.clone())
+    }
+}
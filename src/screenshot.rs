@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::display::{Palette, DISPLAY_WIDTH, DISPLAY_HEIGHT};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScreenshotFormat {
+    //palette coloured and scaled like the window
+    Png,
+    //1 bit per pixel at native resolution, for bug reports and golden tests
+    Pbm,
+    Both,
+}
+
+impl ScreenshotFormat {
+    pub fn parse(text: &str) -> Result<ScreenshotFormat, String> {
+        match text {
+            "png" => Ok(ScreenshotFormat::Png),
+            "pbm" => Ok(ScreenshotFormat::Pbm),
+            "both" => Ok(ScreenshotFormat::Both),
+            _ => Err(format!("unknown screenshot format '{}'", text)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScreenshotConfig {
+    pub dir: PathBuf,
+    pub format: ScreenshotFormat,
+    //take a screenshot automatically once this many frames have run
+    pub at_frame: Option<u64>,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> ScreenshotConfig {
+        ScreenshotConfig {
+            dir: PathBuf::from("."),
+            format: ScreenshotFormat::Both,
+            at_frame: None,
+        }
+    }
+}
+
+fn pixel(display_mem: &[[u8; 64]; 128], x: u32, y: u32) -> bool {
+    display_mem[x as usize][y as usize] != 0
+}
+
+//one byte per output pixel, 0 for background and 1 for foreground, rows top to bottom
+pub fn indexed_frame(display_mem: &[[u8; 64]; 128], scale: u32) -> Vec<u8> {
+    let width = DISPLAY_WIDTH * scale;
+    let height = DISPLAY_HEIGHT * scale;
+
+    let mut data = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            data.push(pixel(display_mem, x / scale, y / scale) as u8);
+        }
+    }
+
+    data
+}
+
+pub fn save_png(path: &Path, display_mem: &[[u8; 64]; 128], palette: &Palette,
+    scale: u32) -> Result<(), String> {
+
+    let width = DISPLAY_WIDTH * scale;
+    let height = DISPLAY_HEIGHT * scale;
+
+    let data: Vec<u8> = indexed_frame(display_mem, scale).iter()
+        .flat_map(|&index| {
+            let color = palette.colors[index as usize];
+            vec![color.r, color.g, color.b]
+        })
+        .collect();
+
+    let file = File::create(path)
+        .map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&data).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+//binary P4 bitmap, rows packed MSB first and padded to a whole byte, 1 is black
+pub fn save_pbm(path: &Path, display_mem: &[[u8; 64]; 128]) -> Result<(), String> {
+    let mut data = format!("P4\n{} {}\n", DISPLAY_WIDTH, DISPLAY_HEIGHT).into_bytes();
+
+    for y in 0..DISPLAY_HEIGHT {
+        let mut byte = 0u8;
+        for x in 0..DISPLAY_WIDTH {
+            if pixel(display_mem, x, y) {
+                byte |= 0x80 >> (x % 8);
+            }
+            if x % 8 == 7 || x == DISPLAY_WIDTH - 1 {
+                data.push(byte);
+                byte = 0;
+            }
+        }
+    }
+
+    File::create(path)
+        .and_then(|mut file| file.write_all(&data))
+        .map_err(|e| format!("could not write {}: {}", path.display(), e))
+}
+
+//chip8-<unix time in ms>.<extension> inside dir
+pub fn timestamped_path(dir: &Path, extension: &str) -> Result<PathBuf, String> {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_millis();
+
+    Ok(dir.join(format!("chip8-{}.{}", millis, extension)))
+}
+
+//writes a .png and/or .pbm with the same timestamped name into the configured directory
+pub fn take_screenshot(display_mem: &[[u8; 64]; 128], config: &ScreenshotConfig,
+    palette: &Palette, scale: u32) -> Result<Vec<PathBuf>, String> {
+
+    let stem = timestamped_path(&config.dir, "png")?;
+
+    let mut written = Vec::new();
+
+    if config.format != ScreenshotFormat::Pbm {
+        let path = stem.with_extension("png");
+        save_png(&path, display_mem, palette, scale)?;
+        written.push(path);
+    }
+
+    if config.format != ScreenshotFormat::Png {
+        let path = stem.with_extension("pbm");
+        save_pbm(&path, display_mem)?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
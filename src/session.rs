@@ -0,0 +1,333 @@
+use crate::capture::Capture;
+use crate::cheats::{Cheat, CheatList};
+use crate::config::Config;
+use crate::coverage::Coverage;
+use crate::frame;
+use crate::hash::fnv1a;
+use crate::history::History;
+use crate::machine::Machine;
+use crate::movie::{MovieHeader, MoviePlayer, MovieWriter, HASH_INTERVAL};
+use crate::profiler::Profiler;
+use crate::recompiler::{Backend, Recompiler};
+use crate::rewind::RewindBuffer;
+use crate::timing::Timing;
+use crate::trace::Tracer;
+
+//everything that happens once per 60 Hz frame regardless of frontend:
+//input recording/replay, running the machine, rewind and reverse step history
+//and screenshots/recordings
+pub struct Session<'a> {
+    pub machine: Machine,
+    pub capture: Capture<'a>,
+    pub cheats: CheatList,
+    movie_writer: Option<MovieWriter>,
+    movie_player: Option<MoviePlayer>,
+    rewind: RewindBuffer,
+    history: History,
+    tracer: Option<Tracer>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    timing: Timing,
+    instructions_per_frame: usize,
+    recompiler: Option<Recompiler>,
+    //whether this frame's key came from the movie being replayed
+    frame_replayed: bool,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(chp8_code: &[u8], config: &'a Config) -> Result<Session<'a>, String> {
+        let rom_hash = fnv1a(chp8_code);
+
+        let movie_player = match &config.replay {
+            Some(path) => {
+                let player = MoviePlayer::load(path)?;
+                if player.header.rom_hash != rom_hash {
+                    return Err(format!("{} was recorded with a different ROM", path.display()));
+                }
+                player.header.check_settings(&config.quirks, config.backend)
+                    .map_err(|e| format!("{} {}", path.display(), e))?;
+                Some(player)
+            },
+            None => None,
+        };
+
+        let seed = match &movie_player {
+            Some(player) => player.header.seed,
+            None => config.seed.unwrap_or_else(rand::random),
+        };
+
+        let movie_writer = match &config.record_input {
+            Some(path) => {
+                let header = MovieHeader {
+                    rom_hash,
+                    seed,
+                    quirks: config.quirks.clone(),
+                    backend: config.backend,
+                    hash_interval: HASH_INTERVAL,
+                };
+                Some(MovieWriter::create(path, &header)?)
+            },
+            None => None,
+        };
+
+        let tracer = match &config.trace.path {
+            Some(path) => Some(Tracer::create(path, &config.trace)?),
+            None => None,
+        };
+
+        //blocks run without stopping between instructions, so nothing that
+        //watches each instruction can be used with them
+        let recompiler = match config.backend {
+            Backend::Interpreter => None,
+            Backend::Recompiler => {
+                if config.trace.path.is_some() || config.profile.enabled() || config.coverage.enabled() {
+                    return Err("the recompiler can't be used with --trace, --profile or --coverage".to_string());
+                }
+                if config.quirks.timing == Timing::Vip {
+                    return Err("the recompiler only supports fixed timing".to_string());
+                }
+                Some(Recompiler::new())
+            },
+        };
+
+        //a movie only holds the keys, so anything else changing memory would
+        //make it play back differently
+        let cheat_dir = if movie_writer.is_some() || movie_player.is_some() {
+            None
+        } else {
+            config.cheat_dir.clone()
+        };
+        let cheats = CheatList::load(&cheat_dir, rom_hash)?;
+
+        let mut machine = Machine::new(chp8_code, seed, &config.quirks)?;
+        cheats.apply_all(&mut machine);
+        let mut rewind = RewindBuffer::new(config.rewind);
+        rewind.push(machine.save_state());
+
+        Ok(Session {
+            machine,
+            capture: Capture::new(config)?,
+            cheats,
+            movie_writer,
+            movie_player,
+            rewind,
+            history: History::new(config.history),
+            tracer,
+            profiler: config.profile.enabled().then(|| Profiler::new(&config.profile)),
+            timing: config.quirks.timing,
+            instructions_per_frame: config.quirks.instructions_per_frame,
+            recompiler,
+            coverage: config.coverage.enabled()
+                .then(|| Coverage::new(&config.coverage, &config.rom_path, chp8_code.len())),
+            frame_replayed: false,
+        })
+    }
+
+    //true once a replay has fed all of its frames
+    pub fn replay_finished(&self) -> bool {
+        self.movie_player.as_ref().is_some_and(|p| p.finished())
+    }
+
+    fn begin_frame(&mut self, live_key: u8) {
+        let replay_key = self.movie_player.as_ref().and_then(|p| p.next_key());
+
+        self.frame_replayed = replay_key.is_some();
+        self.machine.set_key(replay_key.unwrap_or(live_key));
+        for cheat in self.cheats.frozen() {
+            self.history.poke(&mut self.machine, cheat.addr, cheat.value);
+        }
+    }
+
+    //saves the cheat and applies it straight away, so stepping back undoes it
+    pub fn add_cheat(&mut self, cheat: Cheat) -> Result<(), String> {
+        if self.movie_writer.is_some() || self.movie_player.is_some() {
+            return Err("cheats are off while recording or replaying input".to_string());
+        }
+        let (addr, value) = (cheat.addr, cheat.value);
+        self.cheats.add(cheat)?;
+        self.history.poke(&mut self.machine, addr, value);
+        Ok(())
+    }
+
+    fn end_frame(&mut self) -> Result<Option<String>, String> {
+        self.history.tick_timers(&mut self.machine);
+
+        let mut status = None;
+        if let Some(player) = &mut self.movie_player {
+            if self.frame_replayed {
+                player.check_frame(&self.machine)?;
+                if player.finished() {
+                    status = Some(format!("replay finished after {} frames", player.len()));
+                }
+            }
+        }
+
+        if let Some(writer) = &mut self.movie_writer {
+            writer.record_frame(self.machine.held_key(), &self.machine)?;
+        }
+
+        if self.movie_writer.is_none() && self.movie_player.is_none() {
+            self.rewind.push(self.machine.save_state());
+        }
+
+        let capture_status = self.capture.end_frame(&self.machine)?;
+
+        Ok(capture_status.or(status))
+    }
+
+    //frame boundaries are worked out from the machine's counters rather than
+    //the frontend's loop so single stepping in the debugger keeps frames lined
+    //up with normal running
+    fn at_frame_start(&self) -> bool {
+        self.machine.cycle == self.machine.frame_start_cycle
+    }
+
+    fn frame_done(&self) -> bool {
+        frame::frame_done(&self.machine, self.timing, self.instructions_per_frame)
+    }
+
+    fn execute(&mut self, live_key: u8) -> Result<Option<String>, String> {
+        if self.at_frame_start() {
+            self.begin_frame(live_key);
+        }
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(&self.machine)?;
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(&self.machine);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.before_step(&self.machine);
+        }
+
+        let pc = self.machine.reg.PC;
+        let instruction = self.machine.fetch(pc);
+        let before = self.machine.reg.clone();
+        self.history.step(&mut self.machine)
+            .map_err(|e| format!("machine crashed: {}", e))?;
+
+        let waiting_for_vblank = frame::after_step(&mut self.machine, self.timing, instruction, pc, &before);
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.after_step(&self.machine);
+        }
+
+        //an instruction as slow as 00E0 can outlast a whole VIP frame, the
+        //frames it covers still happen, just without running anything
+        let mut status = None;
+        let mut frame_over = waiting_for_vblank || self.frame_done();
+        while frame_over {
+            status = self.end_frame()?.or(status);
+            frame_over = self.frame_done();
+            if frame_over {
+                self.begin_frame(live_key);
+            }
+        }
+
+        Ok(status)
+    }
+
+    //live_key is what the frontend sees held, replays ignore it until they run out
+    pub fn step_instruction(&mut self, live_key: u8) -> Result<Option<String>, String> {
+        let result = self.execute(live_key);
+
+        if result.is_err() {
+            if let Some(tracer) = &mut self.tracer {
+                tracer.dump()?;
+            }
+        }
+
+        result
+    }
+
+    pub fn run_frame(&mut self, live_key: u8) -> Result<Option<String>, String> {
+        if self.recompiler.is_some() {
+            return self.run_frame_compiled(live_key);
+        }
+
+        loop {
+            let status = self.step_instruction(live_key)?;
+            if self.at_frame_start() {
+                return Ok(status);
+            }
+        }
+    }
+
+    //the rest of the frame a block at a time, single steps from the debugger
+    //still go through the interpreter
+    fn run_frame_compiled(&mut self, live_key: u8) -> Result<Option<String>, String> {
+        if self.at_frame_start() {
+            self.begin_frame(live_key);
+        }
+
+        //blocks aren't recorded, undoing single steps from before them would
+        //put back a state that no longer follows on
+        self.history.clear();
+
+        let recompiler = self.recompiler.as_mut().unwrap();
+        let machine = &mut self.machine;
+        let run = machine.cycle - machine.frame_start_cycle;
+        recompiler.run(machine, (self.instructions_per_frame as u64).saturating_sub(run))
+            .map_err(|e| format!("machine crashed: {}", e))?;
+
+        self.end_frame()
+    }
+
+    //undoes one instruction, false when there is nothing left to undo
+    pub fn step_back(&mut self) -> Result<bool, String> {
+        if self.movie_writer.is_some() || self.movie_player.is_some() {
+            return Err("reverse stepping is off while recording or replaying input".to_string());
+        }
+
+        Ok(self.history.step_back(&mut self.machine))
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    //steps one frame back in time instead of running one, for while the rewind key is held
+    pub fn rewind_frame(&mut self) -> Result<Option<String>, String> {
+        //going back would make the movie and the machine disagree about the past
+        if self.movie_writer.is_some() || self.movie_player.is_some() {
+            return Ok(Some("rewind is off while recording or replaying input".to_string()));
+        }
+
+        if !self.rewind.enabled() {
+            return Ok(Some("rewind is off".to_string()));
+        }
+
+        let status = match self.rewind.pop() {
+            Some(state) => {
+                self.machine.load_state(&state)?;
+                //instruction history can't be undone past a jump in time
+                self.history.clear();
+                None
+            },
+            None => Some("no more rewind history".to_string()),
+        };
+
+        let capture_status = self.capture.end_frame(&self.machine)?;
+
+        Ok(capture_status.or(status))
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        if let Some(profiler) = &self.profiler {
+            profiler.finish(&self.machine)?;
+        }
+        if let Some(coverage) = &self.coverage {
+            coverage.finish(&self.machine)?;
+        }
+        if let Some(tracer) = self.tracer {
+            tracer.finish()?;
+        }
+
+        if let Some(writer) = self.movie_writer {
+            writer.finish()?;
+        }
+
+        self.capture.finish()
+    }
+}
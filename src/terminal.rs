@@ -0,0 +1,283 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use ratatui::{
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind, KeyboardEnhancementFlags,
+            PushKeyboardEnhancementFlags, PopKeyboardEnhancementFlags},
+        execute,
+        terminal::supports_keyboard_enhancement,
+    },
+    layout::{Constraint, Layout},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    Frame,
+};
+
+use crate::config::Config;
+use crate::debugger::Debugger;
+use crate::display::{self, Palette, DISPLAY_WIDTH, DISPLAY_HEIGHT};
+use crate::governor::Governor;
+use crate::phosphor::{Intensity, Phosphor};
+use crate::session::Session;
+
+//most terminals only send key presses, so a key counts as held until it
+//hasn't repeated for this long
+const KEY_HOLD: Duration = Duration::from_millis(150);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Glyphs {
+    //one cell per 1x2 pixels, 64x32 draws as 64x16 cells
+    #[default]
+    HalfBlock,
+    //one cell per 2x4 pixels of the whole 128x64 display memory, so hires
+    //output fits, drawn as 64x16 cells without phosphor
+    Braille,
+}
+
+fn keypad_index(key: char) -> Option<u8> {
+    let index = match key.to_ascii_lowercase() {
+        '1' => 0x01,
+        '2' => 0x02,
+        '3' => 0x03,
+        '4' => 0x0c,
+        'q' => 0x04,
+        'w' => 0x05,
+        'e' => 0x06,
+        'r' => 0x0d,
+        'a' => 0x07,
+        's' => 0x08,
+        'd' => 0x09,
+        'f' => 0x0e,
+        'z' => 0x0a,
+        'x' => 0x00,
+        'c' => 0x0b,
+        'v' => 0x0f,
+        _ => return None,
+    };
+
+    Some(index)
+}
+
+fn tui_color(color: sdl2::pixels::Color) -> Color {
+    Color::Rgb(color.r, color.g, color.b)
+}
+
+fn half_block_lines(intensity: &Intensity, palette: &Palette) -> Vec<Line<'static>> {
+    let shade = |amount: f32| {
+        tui_color(display::blend(palette.background(), palette.foreground(), amount))
+    };
+
+    (0..DISPLAY_HEIGHT as usize / 2).map(|row| {
+        let spans: Vec<Span> = (0..DISPLAY_WIDTH as usize).map(|x| {
+            let top = intensity[x][row * 2];
+            let bottom = intensity[x][row * 2 + 1];
+
+            //upper half is the foreground colour, lower half the background colour
+            Span::styled("\u{2580}", Style::default().fg(shade(top)).bg(shade(bottom)))
+        }).collect();
+
+        Line::from(spans)
+    }).collect()
+}
+
+fn braille_lines(display_mem: &[[u8; 64]; 128], palette: &Palette) -> Vec<Line<'static>> {
+    //dot bit for each (x, y) offset inside a 2x4 braille cell
+    const DOTS: [[u32; 4]; 2] = [
+        [0x01, 0x02, 0x04, 0x40],
+        [0x08, 0x10, 0x20, 0x80],
+    ];
+
+    let style = Style::default()
+        .fg(tui_color(palette.foreground()))
+        .bg(tui_color(palette.background()));
+
+    (0..display_mem[0].len() / 4).map(|row| {
+        let text: String = (0..display_mem.len() / 2).map(|col| {
+            let mut bits = 0;
+            for dx in 0..2 {
+                for dy in 0..4 {
+                    if display_mem[col * 2 + dx][row * 4 + dy] != 0 {
+                        bits |= DOTS[dx][dy];
+                    }
+                }
+            }
+
+            std::char::from_u32(0x2800 + bits).unwrap()
+        }).collect();
+
+        Line::styled(text, style)
+    }).collect()
+}
+
+fn draw(frame: &mut Frame, session: &Session, debugger: &Debugger, lines: Vec<Line>, status: &str) {
+    let width = lines[0].width() as u16 + 2;
+    let height = lines.len() as u16 + 2;
+
+    let [screen, debug] = Layout::horizontal([Constraint::Length(width), Constraint::Min(48)])
+        .areas(frame.area());
+    let [screen, _] = Layout::vertical([Constraint::Length(height), Constraint::Min(0)])
+        .areas(screen);
+
+    let block = Block::bordered().title(" CHIP-8 ").title_bottom(status);
+    frame.render_widget(Paragraph::new(lines).block(block), screen);
+
+    debugger.render(frame, debug, session);
+}
+
+fn run(terminal: &mut ratatui::DefaultTerminal, session: &mut Session, governor: &mut Governor,
+    config: &Config, key_releases: bool) -> Result<(), String> {
+
+    let mut phosphor = Phosphor::new(config.display.phosphor);
+    let mut status = String::new();
+
+    let mut held_key: Option<u8> = None;
+    let mut held_until = Instant::now();
+    let mut rewinding = false;
+    let mut rewind_until = Instant::now();
+    let mut fast_forward_until = Instant::now();
+    let mut beeping = false;
+    let mut debugger = Debugger::new();
+
+    loop {
+        let frame_start = Instant::now();
+
+        while event::poll(Duration::ZERO).map_err(|e| e.to_string())? {
+            if let Event::Key(key) = event::read().map_err(|e| e.to_string())? {
+                if debugger.handle_key(&key, session)? {
+                    continue;
+                }
+
+                if key.code == KeyCode::Esc {
+                    return Ok(());
+                }
+
+                if key.kind == KeyEventKind::Press {
+                    match key.code {
+                        KeyCode::F(12) => session.capture.request_screenshot(),
+                        KeyCode::F(9) => session.capture.toggle_recording(),
+                        _ => {}
+                    }
+                }
+
+                if key.code == KeyCode::Backspace {
+                    rewinding = key.kind != KeyEventKind::Release;
+                    rewind_until = frame_start + KEY_HOLD;
+                }
+
+                if key.code == KeyCode::Tab {
+                    governor.fast_forward = key.kind != KeyEventKind::Release;
+                    fast_forward_until = frame_start + KEY_HOLD;
+                }
+
+                let index = match key.code {
+                    KeyCode::Char(c) => keypad_index(c),
+                    _ => None,
+                };
+
+                if let Some(index) = index {
+                    if key.kind == KeyEventKind::Release {
+                        if held_key == Some(index) {
+                            held_key = None;
+                        }
+                    } else {
+                        held_key = Some(index);
+                        held_until = frame_start + KEY_HOLD;
+                    }
+                }
+            }
+        }
+
+        if !key_releases && held_until <= frame_start {
+            held_key = None;
+        }
+        if !key_releases && rewind_until <= frame_start {
+            rewinding = false;
+        }
+        if !key_releases && fast_forward_until <= frame_start {
+            governor.fast_forward = false;
+        }
+
+        let message = if debugger.paused {
+            None
+        } else if rewinding {
+            session.rewind_frame()?
+        } else {
+            let mut message = None;
+            while governor.frame_due() {
+                message = session.run_frame(held_key.unwrap_or(0xff))?.or(message);
+            }
+            message
+        };
+
+        if let Some(message) = message {
+            status = format!(" {} ", message);
+        }
+
+        let machine = &session.machine;
+
+        //terminal bell once at the start of each sound
+        if machine.reg.ST > 0 && !beeping {
+            let mut stdout = io::stdout();
+            stdout.write_all(b"\x07").and_then(|_| stdout.flush())
+                .map_err(|e| e.to_string())?;
+        }
+        beeping = machine.reg.ST > 0;
+
+        let lines = match config.glyphs {
+            Glyphs::HalfBlock => half_block_lines(phosphor.update(&machine.display_mem), &config.display.palette),
+            Glyphs::Braille => braille_lines(&machine.display_mem, &config.display.palette),
+        };
+
+        terminal.draw(|frame| draw(frame, session, &debugger, lines, &status))
+            .map_err(|e| e.to_string())?;
+
+        governor.wait();
+    }
+}
+
+pub fn chp8_execute_terminal(chp8_code: &[u8], config: &Config) -> Result<(), String> {
+    let mut session = Session::new(chp8_code, config)?;
+    let mut terminal = ratatui::init();
+
+    //terminals implementing the kitty keyboard protocol report real key releases
+    let key_releases = supports_keyboard_enhancement().unwrap_or(false);
+    if key_releases {
+        execute!(io::stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))
+            .map_err(|e| e.to_string())?;
+    }
+
+    let mut governor = Governor::new(&config.speed);
+    let result = run(&mut terminal, &mut session, &mut governor, config, key_releases);
+
+    if key_releases {
+        let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+    }
+    ratatui::restore();
+
+    if config.speed.benchmark {
+        println!("{}", governor.summary(&session.machine));
+    }
+
+    result.and(session.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braille_covers_the_whole_display_memory() {
+        let mut display_mem = [[0u8; 64]; 128];
+        display_mem[0][0] = 1;
+        display_mem[127][63] = 1;
+
+        let lines = braille_lines(&display_mem, &Palette::default());
+        assert_eq!(lines.len(), 16);
+        let rows: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        assert_eq!(rows[0].chars().count(), 64);
+        assert_eq!(rows[0].chars().next(), Some('\u{2801}'));
+        assert_eq!(rows[15].chars().last(), Some('\u{2880}'));
+    }
+}
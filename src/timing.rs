@@ -0,0 +1,98 @@
+use crate::machine::Registers;
+
+//How long a frame is. Fixed runs the profile's instructions per frame
+//whatever they are. Vip charges each instruction what it took the COSMAC VIP
+//interpreter, in 1802 machine cycles of 8 clocks, and ends the frame when the
+//time until the next 60 Hz display interrupt has been used up, so slow
+//instructions like Dxyn and 00E0 slow a game down the way they did on hardware.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timing {
+    Fixed,
+    Vip,
+}
+
+impl Timing {
+    pub fn parse(text: &str) -> Result<Timing, String> {
+        match text {
+            "fixed" => Ok(Timing::Fixed),
+            "vip" => Ok(Timing::Vip),
+            _ => Err(format!("unknown timing '{}'", text)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Timing::Fixed => "fixed",
+            Timing::Vip => "vip",
+        }
+    }
+}
+
+//1.76 MHz clock / 8 clocks per machine cycle / 60 Hz
+const VIP_CYCLES_PER_FRAME: u64 = 3668;
+//the 1861 steals one cycle per byte shown, 256 bytes repeated on 4 scanlines each
+const VIP_DMA_CYCLES: u64 = 1024;
+//the interrupt routine that sets up DMA and counts the timers down
+const VIP_INTERRUPT_CYCLES: u64 = 32;
+
+//machine cycles the interpreter gets between two display interrupts
+pub const VIP_FRAME_CYCLES: u64 = VIP_CYCLES_PER_FRAME - VIP_DMA_CYCLES - VIP_INTERRUPT_CYCLES;
+
+//fetching and dispatching an instruction, paid by every instruction
+const VIP_FETCH_CYCLES: u64 = 40;
+
+//what one instruction cost on the VIP, given the registers before and after it ran
+pub fn vip_cycles(instruction: u16, before: &Registers, after: &Registers) -> u64 {
+    let x = ((instruction >> 8) & 0xf) as usize;
+    let kk = instruction & 0xff;
+    let n = (instruction & 0xf) as u64;
+    let skipped = after.PC == before.PC.wrapping_add(4);
+    let skip_cost = |base: u64| if skipped { base + 4 } else { base };
+
+    let execute = match (instruction >> 12, kk) {
+        //clears the 256 display bytes one at a time
+        (0x0, 0xe0) => 24 + 256 * 12,
+        (0x0, 0xee) => 10,
+        //machine code subroutines aren't run, treat them as a no-op
+        (0x0, _) => 0,
+        (0x1, _) => 12,
+        (0x2, _) => 26,
+        (0x3, _) | (0x4, _) => skip_cost(10),
+        (0x5, _) | (0x9, _) => skip_cost(14),
+        (0x6, _) => 6,
+        (0x7, _) => 10,
+        (0x8, kk) if kk & 0xf == 0 => 12,
+        (0x8, _) => 44,
+        (0xa, _) => 12,
+        (0xb, _) => {
+            //an extra two cycles when adding V0 carries into the high byte
+            let target = (instruction & 0x0fff) + before.V[0] as u16;
+            if target >> 8 != (instruction & 0x0fff) >> 8 { 24 } else { 22 }
+        },
+        (0xc, _) => 36,
+        //sprite rows are shifted into place, which takes longer when the
+        //sprite straddles two display bytes
+        (0xd, _) => {
+            let aligned = before.V[x].is_multiple_of(8);
+            26 + n * if aligned { 34 } else { 46 }
+        },
+        (0xe, _) => skip_cost(14),
+        (0xf, 0x07) => 10,
+        //each check of the keypad while waiting
+        (0xf, 0x0a) => 18,
+        (0xf, 0x15) | (0xf, 0x18) => 10,
+        (0xf, 0x1e) => 16,
+        (0xf, 0x29) => 16,
+        //digits are found by repeated subtraction, so bigger numbers take longer
+        (0xf, 0x33) => {
+            let value = before.V[x];
+            let digits = (value / 100 + (value / 10) % 10 + value % 10) as u64;
+            80 + 16 * digits
+        },
+        (0xf, 0x55) | (0xf, 0x65) => 14 + 14 * (x as u64 + 1),
+        _ => 0,
+    };
+
+    VIP_FETCH_CYCLES + execute
+}
@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use crate::disasm;
+use crate::machine::Machine;
+
+//Text traces have one line per instruction with fixed width columns, state is
+//taken before the instruction runs:
+//
+//  cycle      pc   op   v0 v1 .. vf                                     i    sp   dt st mnemonic
+//  0000000000 0200 00E0 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 0000 0FA0 00 00 CLS
+//
+//Binary traces start with BINARY_MAGIC followed by fixed size big endian records.
+
+pub const TEXT_HEADER: &str = "# cycle pc op v0 v1 v2 v3 v4 v5 v6 v7 v8 v9 va vb vc vd ve vf i sp dt st mnemonic";
+pub const BINARY_MAGIC: &[u8; 4] = b"C8T1";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceFormat {
+    Text,
+    Binary,
+}
+
+impl TraceFormat {
+    pub fn parse(text: &str) -> Result<TraceFormat, String> {
+        match text {
+            "text" => Ok(TraceFormat::Text),
+            "binary" => Ok(TraceFormat::Binary),
+            _ => Err(format!("unknown trace format '{}'", text)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TraceConfig {
+    pub path: Option<PathBuf>,
+    pub format: TraceFormat,
+    //only trace instructions with lo <= PC <= hi
+    pub range: Option<(u16, u16)>,
+    //keep only the last N entries in memory and write them out on error
+    pub ring: Option<usize>,
+}
+
+impl Default for TraceConfig {
+    fn default() -> TraceConfig {
+        TraceConfig {
+            path: None,
+            format: TraceFormat::Text,
+            range: None,
+            ring: None,
+        }
+    }
+}
+
+//"200-2ff", both ends inclusive and in hex
+pub fn parse_range(text: &str) -> Result<(u16, u16), String> {
+    let bad_range = || format!("bad address range '{}', expected e.g. 200-2ff", text);
+
+    let (lo, hi) = text.split_once('-').ok_or_else(bad_range)?;
+    let lo = u16::from_str_radix(lo.trim_start_matches("0x"), 16).map_err(|_| bad_range())?;
+    let hi = u16::from_str_radix(hi.trim_start_matches("0x"), 16).map_err(|_| bad_range())?;
+
+    Ok((lo, hi))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub sp: u16,
+    pub dt: u8,
+    pub st: u8,
+}
+
+impl TraceEntry {
+    pub fn capture(machine: &Machine) -> TraceEntry {
+        let reg = &machine.reg;
+        TraceEntry {
+            cycle: machine.cycle,
+            pc: reg.PC,
+            opcode: machine.fetch(reg.PC),
+            v: reg.V,
+            i: reg.I,
+            sp: reg.SP,
+            dt: reg.DT,
+            st: reg.ST,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let registers: Vec<String> = self.v.iter().map(|v| format!("{:02X}", v)).collect();
+
+        format!("{:010} {:04X} {:04X} {} {:04X} {:04X} {:02X} {:02X} {}",
+            self.cycle, self.pc, self.opcode, registers.join(" "),
+            self.i, self.sp, self.dt, self.st, disasm::mnemonic(self.opcode))
+    }
+
+    pub fn write_binary<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        out.write_u64::<BigEndian>(self.cycle)?;
+        out.write_u16::<BigEndian>(self.pc)?;
+        out.write_u16::<BigEndian>(self.opcode)?;
+        out.write_all(&self.v)?;
+        out.write_u16::<BigEndian>(self.i)?;
+        out.write_u16::<BigEndian>(self.sp)?;
+        out.write_u8(self.dt)?;
+        out.write_u8(self.st)
+    }
+}
+
+pub struct Tracer {
+    out: BufWriter<File>,
+    format: TraceFormat,
+    range: Option<(u16, u16)>,
+    ring: Option<(VecDeque<TraceEntry>, usize)>,
+}
+
+impl Tracer {
+    pub fn create(path: &Path, config: &TraceConfig) -> Result<Tracer, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+        let mut out = BufWriter::new(file);
+
+        let header = match config.format {
+            TraceFormat::Text => writeln!(out, "{}", TEXT_HEADER),
+            TraceFormat::Binary => out.write_all(BINARY_MAGIC),
+        };
+        header.map_err(|e| e.to_string())?;
+
+        Ok(Tracer {
+            out,
+            format: config.format,
+            range: config.range,
+            ring: config.ring.map(|size| (VecDeque::with_capacity(size), size)),
+        })
+    }
+
+    fn write(&mut self, entry: &TraceEntry) -> Result<(), String> {
+        let result = match self.format {
+            TraceFormat::Text => writeln!(self.out, "{}", entry.to_text()),
+            TraceFormat::Binary => entry.write_binary(&mut self.out),
+        };
+        result.map_err(|e| e.to_string())
+    }
+
+    //call before each instruction runs
+    pub fn record(&mut self, machine: &Machine) -> Result<(), String> {
+        if let Some((lo, hi)) = self.range {
+            if machine.reg.PC < lo || machine.reg.PC > hi {
+                return Ok(());
+            }
+        }
+
+        let entry = TraceEntry::capture(machine);
+
+        match &mut self.ring {
+            Some((entries, size)) => {
+                if entries.len() == *size {
+                    entries.pop_front();
+                }
+                entries.push_back(entry);
+                Ok(())
+            },
+            None => self.write(&entry),
+        }
+    }
+
+    //writes out what the ring buffer holds, a no-op when not in ring mode
+    pub fn dump(&mut self) -> Result<(), String> {
+        if let Some((entries, _)) = self.ring.take() {
+            for entry in &entries {
+                self.write(entry)?;
+            }
+        }
+
+        self.out.flush().map_err(|e| e.to_string())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        //ring mode only writes on error
+        self.ring = None;
+        self.out.flush().map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::VecDeque;
+use std::fs;
+
+use crate::config::{Config, Frontend};
+use crate::session::Session;
+use crate::trace::{TraceEntry, TEXT_HEADER};
+
+//Runs a ROM headless and checks every instruction against a trace logged by
+//another emulator. Reference logs are read one instruction per line, split on
+//whitespace and commas; --columns names what each column holds so logs in
+//other layouts can be compared without converting them first.
+
+const USAGE: &str = "usage: chip8 trace-diff [--columns LIST] [--context N] [--seed N] \
+[--quirks vip|chip48|schip|octo] [--ipf N] [--timing fixed|vip] [--key HEX] <rom> <reference>
+
+LIST names the reference columns in order, '-' skips a column and v0-vf
+expands to all sixteen registers. Columns past the end of the list are
+ignored. Values are hex, except cycle which is decimal, and may carry a
+label like PC:0200 or v3=1f. The default matches chip8 --trace output:
+cycle,pc,op,v0-vf,i,sp,dt,st. --quirks, --ipf and --timing set up the
+machine like the chip8 options of the same name, and --key holds a key down
+for the whole run.";
+
+const DEFAULT_COLUMNS: &str = "cycle,pc,op,v0-vf,i,sp,dt,st";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Column {
+    Skip,
+    Cycle,
+    Pc,
+    Opcode,
+    V(usize),
+    I,
+    Sp,
+    Dt,
+    St,
+}
+
+impl Column {
+    fn name(&self) -> String {
+        match self {
+            Column::Skip => "-".to_string(),
+            Column::Cycle => "cycle".to_string(),
+            Column::Pc => "pc".to_string(),
+            Column::Opcode => "op".to_string(),
+            Column::V(x) => format!("v{:x}", x),
+            Column::I => "i".to_string(),
+            Column::Sp => "sp".to_string(),
+            Column::Dt => "dt".to_string(),
+            Column::St => "st".to_string(),
+        }
+    }
+
+    fn value(&self, entry: &TraceEntry) -> u64 {
+        match self {
+            Column::Skip => 0,
+            Column::Cycle => entry.cycle,
+            Column::Pc => entry.pc as u64,
+            Column::Opcode => entry.opcode as u64,
+            Column::V(x) => entry.v[*x] as u64,
+            Column::I => entry.i as u64,
+            Column::Sp => entry.sp as u64,
+            Column::Dt => entry.dt as u64,
+            Column::St => entry.st as u64,
+        }
+    }
+}
+
+fn parse_columns(text: &str) -> Result<Vec<Column>, String> {
+    let mut columns = Vec::new();
+
+    for name in text.split(',') {
+        let name = name.trim().to_ascii_lowercase();
+        match name.as_str() {
+            "-" | "mnemonic" => columns.push(Column::Skip),
+            "cycle" => columns.push(Column::Cycle),
+            "pc" => columns.push(Column::Pc),
+            "op" | "opcode" => columns.push(Column::Opcode),
+            "v0-vf" => columns.extend((0..16).map(Column::V)),
+            "i" => columns.push(Column::I),
+            "sp" => columns.push(Column::Sp),
+            "dt" => columns.push(Column::Dt),
+            "st" => columns.push(Column::St),
+            _ => {
+                let x = name.strip_prefix('v')
+                    .filter(|x| x.len() == 1)
+                    .and_then(|x| usize::from_str_radix(x, 16).ok())
+                    .ok_or(format!("unknown trace column '{}'", name))?;
+                columns.push(Column::V(x));
+            },
+        }
+    }
+
+    Ok(columns)
+}
+
+//one reference line, the values of the mapped columns in mapping order
+struct RefLine {
+    number: usize,
+    text: String,
+    values: Vec<(Column, u64)>,
+}
+
+fn parse_line(columns: &[Column], number: usize, text: &str) -> Result<RefLine, String> {
+    let fields: Vec<&str> = text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|field| !field.is_empty())
+        .collect();
+
+    let mut values = Vec::new();
+    for (column, field) in columns.iter().zip(fields.iter()) {
+        if *column == Column::Skip {
+            continue;
+        }
+
+        //"PC:0200" and "v3=1f" style labels
+        let field = field.rsplit([':', '=']).next().unwrap_or(field);
+        let field = field.trim_start_matches("0x");
+
+        let value = match column {
+            Column::Cycle => field.parse().ok(),
+            _ => u64::from_str_radix(field, 16).ok(),
+        };
+        let value = value.ok_or(format!("line {}: bad {} value '{}'", number, column.name(), field))?;
+
+        values.push((*column, value));
+    }
+
+    if values.len() < columns.iter().filter(|c| **c != Column::Skip).count() {
+        return Err(format!("line {}: expected {} columns, got {}", number, columns.len(), fields.len()));
+    }
+
+    Ok(RefLine { number, text: text.to_string(), values })
+}
+
+struct Options {
+    columns: Vec<Column>,
+    context: usize,
+    seed: u64,
+    //--quirks, --ipf and --timing, passed on to the config as given
+    machine_options: Vec<(String, String)>,
+    key: u8,
+    rom_path: String,
+    reference_path: String,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut columns = parse_columns(DEFAULT_COLUMNS)?;
+    let mut context = 5;
+    let mut seed = 0;
+    let mut machine_options = Vec::new();
+    let mut held_key = 0xff;
+    let mut paths = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        if let Some(key) = arg.strip_prefix("--") {
+            let value = iter.next().ok_or(USAGE.to_string())?;
+            match key {
+                "columns" => columns = parse_columns(value)?,
+                "context" => context = value.parse()
+                    .map_err(|_| format!("bad context line count '{}'", value))?,
+                "seed" => seed = value.parse()
+                    .map_err(|_| format!("bad seed '{}'", value))?,
+                "quirks" | "ipf" | "timing" => machine_options.push((key.to_string(), value.clone())),
+                "key" => held_key = u8::from_str_radix(value, 16).ok()
+                    .filter(|key| *key < 16)
+                    .ok_or(format!("bad key '{}'", value))?,
+                _ => return Err(format!("unknown option '{}'\n{}", key, USAGE)),
+            }
+        } else {
+            paths.push(arg.clone());
+        }
+    }
+
+    if paths.len() != 2 {
+        return Err(USAGE.to_string());
+    }
+
+    Ok(Options {
+        columns,
+        context,
+        seed,
+        machine_options,
+        key: held_key,
+        reference_path: paths.pop().unwrap(),
+        rom_path: paths.pop().unwrap(),
+    })
+}
+
+//args are everything after "trace-diff", Ok(true) when the traces match
+pub fn run(args: &[String]) -> Result<bool, String> {
+    let options = parse_args(args)?;
+
+    let chp8_code = fs::read(&options.rom_path)
+        .map_err(|e| format!("could not read {}: {}", options.rom_path, e))?;
+    let reference = fs::read_to_string(&options.reference_path)
+        .map_err(|e| format!("could not read {}: {}", options.reference_path, e))?;
+
+    let mut ref_lines = Vec::new();
+    for (num, line) in reference.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        ref_lines.push(parse_line(&options.columns, num + 1, line)?);
+    }
+
+    let mut config = Config {
+        rom_path: options.rom_path.clone(),
+        frontend: Frontend::Headless,
+        seed: Some(options.seed),
+        history: 0,
+        cheat_dir: None,
+        ..Config::default()
+    };
+    config.apply_all(&options.machine_options)?;
+    let mut session = Session::new(&chp8_code, &config)?;
+
+    let mut previous: VecDeque<(TraceEntry, &RefLine)> = VecDeque::new();
+
+    for (index, ref_line) in ref_lines.iter().enumerate() {
+        let entry = TraceEntry::capture(&session.machine);
+
+        let mismatches: Vec<String> = ref_line.values.iter()
+            .filter(|(column, value)| column.value(&entry) != *value)
+            .map(|(column, value)| format!("{} is {:x}, reference has {:x}",
+                column.name(), column.value(&entry), value))
+            .collect();
+
+        if !mismatches.is_empty() {
+            report(&options, &mut session, &previous, &ref_lines[index..], &mismatches);
+            return Ok(false);
+        }
+
+        if previous.len() == options.context {
+            previous.pop_front();
+        }
+        if options.context > 0 {
+            previous.push_back((entry, ref_line));
+        }
+
+        if let Err(e) = session.step_instruction(options.key) {
+            println!("reference line {} matched but the next instruction failed: {}", ref_line.number, e);
+            return Ok(false);
+        }
+    }
+
+    println!("{} instructions match {}", ref_lines.len(), options.reference_path);
+    session.finish()?;
+
+    Ok(true)
+}
+
+fn report(options: &Options, session: &mut Session, previous: &VecDeque<(TraceEntry, &RefLine)>,
+    remaining: &[RefLine], mismatches: &[String]) {
+
+    let ours = TraceEntry::capture(&session.machine);
+
+    println!("first divergence at cycle {}, reference line {}:", ours.cycle, remaining[0].number);
+    for mismatch in mismatches {
+        println!("  {}", mismatch);
+    }
+    println!();
+    println!("  {}", TEXT_HEADER);
+
+    for (entry, ref_line) in previous {
+        println!("  {}", entry.to_text());
+        println!("  {:>5}: {}", ref_line.number, ref_line.text);
+    }
+
+    println!("> {}", ours.to_text());
+    println!("> {:>5}: {}", remaining[0].number, remaining[0].text);
+
+    //both sides keep going from where they split so the effect is visible
+    println!();
+    println!("  after:");
+    for ref_line in remaining.iter().skip(1).take(options.context) {
+        if session.step_instruction(options.key).is_err() {
+            break;
+        }
+        println!("  {}", TraceEntry::capture(&session.machine).to_text());
+        println!("  {:>5}: {}", ref_line.number, ref_line.text);
+    }
+}
@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::Path;
+
+use chip8::frame::FrameRunner;
+use chip8::machine::Machine;
+use chip8::quirks::Quirks;
+use chip8::recompiler::Backend;
+
+//runs the ROM on both backends with the same keys and checks the machines
+//agree after every frame
+fn assert_backends_agree(chp8_code: &[u8], frames: usize) {
+    let quirks = Quirks::default();
+    let mut interpreter = Machine::new(chp8_code, 1, &quirks).unwrap();
+    let mut recompiler = Machine::new(chp8_code, 1, &quirks).unwrap();
+    let mut interpreter_runner = FrameRunner::new(&quirks, Backend::Interpreter).unwrap();
+    let mut recompiler_runner = FrameRunner::new(&quirks, Backend::Recompiler).unwrap();
+
+    for frame in 0..frames {
+        //a key held for a while then released, like a player would
+        let keys = if frame % 20 < 10 { 1 << (frame / 20 % 16) } else { 0 };
+        interpreter_runner.run_frame(&mut interpreter, keys).unwrap();
+        recompiler_runner.run_frame(&mut recompiler, keys).unwrap();
+
+        assert_eq!(recompiler.state_hash(), interpreter.state_hash(), "backends diverge on frame {}", frame);
+    }
+}
+
+#[test]
+fn backends_agree_on_ibm_logo() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("ibm.ch8");
+    let chp8_code = fs::read(&path).unwrap();
+    assert_backends_agree(&chp8_code, 120);
+}
+
+#[test]
+fn backends_agree_on_self_modifying_code() {
+    let chp8_code = [
+        0x12, 0x0c, //200: jump to the loop
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x62, 0x05, //20c: v2 = 5, patched to v2 += 1 after the first time
+        0x73, 0x01, //20e: v3 += 1
+        0x60, 0x72, //210: v0 = 72
+        0x61, 0x01, //212: v1 = 01
+        0xa2, 0x0c, //214: i = 20c
+        0xf1, 0x55, //216: write v0 and v1 over 20c
+        0x12, 0x0c, //218: jump to 20c
+    ];
+    assert_backends_agree(&chp8_code, 60);
+
+    //the patched instruction really ran
+    let quirks = Quirks::default();
+    let mut machine = Machine::new(&chp8_code, 1, &quirks).unwrap();
+    let mut runner = FrameRunner::new(&quirks, Backend::Recompiler).unwrap();
+    runner.run_frame(&mut machine, 0).unwrap();
+    assert_eq!(machine.memory[0x20c..0x20e], [0x72, 0x01]);
+    assert!(machine.reg.V[2] > 5);
+}
@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use chip8::batch::Batch;
+use chip8::environment::{EnvSpec, Environment, OBSERVATION_SIZE};
+
+//counts up the byte at 300 while key 5 is held
+const COUNTER_ROM: [u8; 12] = [
+    0xa3, 0x00, //200: i = 300
+    0x65, 0x05, //202: v5 = 5
+    0xe5, 0xa1, //204: skip unless key 5 is down
+    0x70, 0x01, //206: v0 += 1
+    0xf0, 0x55, //208: write v0 to 300
+    0x12, 0x00, //20a: loop
+];
+
+//returns with an empty stack while key 1 is held
+const CRASH_ROM: [u8; 8] = [
+    0x61, 0x01, //200: v1 = 1
+    0xe1, 0x9e, //202: skip if key 1 is down
+    0x12, 0x02, //204: loop
+    0x00, 0xee, //206: return with nothing to return to
+];
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chip8-env-{}-{}", name, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn documented_spec_loads() {
+    let dir = temp_dir("doc");
+    let path = dir.join("pong.env");
+    fs::write(&path, "\
+rom = pong.ch8
+frame-skip = 4
+max-frames = 18000
+# score, three digits as Fx33 stores them
+reward = bcd 2f4
+# lives, losing one costs 10
+reward = byte 2f0 -10
+done = byte 2f0 eq 0
+").unwrap();
+
+    let spec = EnvSpec::load(path.to_str().unwrap()).unwrap();
+    assert_eq!(spec.frame_skip, 4);
+    assert_eq!(spec.max_frames, Some(18000));
+    assert_eq!(PathBuf::from(&spec.rom_path), dir.join("pong.ch8"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn steps_a_rom_with_held_keys() {
+    let dir = temp_dir("step");
+    fs::write(dir.join("counter.ch8"), COUNTER_ROM).unwrap();
+    let path = dir.join("counter.env");
+    fs::write(&path, "\
+rom = counter.ch8
+quirks = schip
+ipf = 10
+frame-skip = 2
+reward = byte 300
+done = byte 300 ge 40
+").unwrap();
+
+    let spec = EnvSpec::load(path.to_str().unwrap()).unwrap();
+    let mut env = Environment::new(spec, 0).unwrap();
+    assert_eq!(env.reset().unwrap().len(), OBSERVATION_SIZE);
+
+    //other keys don't count
+    let step = env.step(1 << 7).unwrap();
+    assert_eq!(step.reward, 0.0);
+    assert!(!step.done);
+
+    //key 5 held together with another one still does
+    let step = env.step(1 << 5 | 1 << 7).unwrap();
+    assert!(step.reward > 0.0);
+    assert_eq!(step.observation.len(), OBSERVATION_SIZE);
+
+    let mut steps = 0;
+    while !env.step(1 << 5).unwrap().done {
+        steps += 1;
+        assert!(steps < 100, "the episode never ended");
+    }
+    assert!(env.machine.memory[0x300] >= 40);
+    assert!(env.step(1 << 5).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn batch_matches_across_thread_counts() {
+    let dir = temp_dir("batch");
+    fs::write(dir.join("counter.ch8"), COUNTER_ROM).unwrap();
+    let path = dir.join("counter.env");
+    fs::write(&path, "rom = counter.ch8\nreward = byte 300\ndone = byte 300 ge 30\n").unwrap();
+    let spec = EnvSpec::load(path.to_str().unwrap()).unwrap();
+
+    let run = |threads| {
+        let mut batch = Batch::new(&spec, 5, 0, threads).unwrap();
+        assert_eq!(batch.reset().unwrap().len(), 5 * OBSERVATION_SIZE);
+
+        let mut rewards = Vec::new();
+        for step in 0..20 {
+            //only some instances hold key 5, and which ones changes
+            let keys: Vec<u16> = (0..5).map(|i| if (i + step) % 3 == 0 { 0 } else { 1 << 5 }).collect();
+            let result = batch.step(&keys).unwrap();
+            assert_eq!(result.observations.len(), 5 * OBSERVATION_SIZE);
+            rewards.push((result.rewards, result.done));
+        }
+        rewards
+    };
+
+    let single = run(1);
+    assert!(single.iter().any(|(_, done)| done.contains(&true)));
+    assert_eq!(run(2), single);
+    assert_eq!(run(5), single);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn batch_keeps_running_when_an_instance_crashes() {
+    let dir = temp_dir("crash");
+    fs::write(dir.join("crash.ch8"), CRASH_ROM).unwrap();
+    let path = dir.join("crash.env");
+    fs::write(&path, "rom = crash.ch8\nmax-frames = 1000\n").unwrap();
+    let spec = EnvSpec::load(path.to_str().unwrap()).unwrap();
+
+    let mut batch = Batch::new(&spec, 3, 0, 2).unwrap();
+    batch.reset().unwrap();
+
+    let step = batch.step(&[0, 1 << 1, 0]).unwrap();
+    assert_eq!(step.done, vec![false, true, false]);
+    assert!(step.errors[1].as_ref().unwrap().contains("empty stack"));
+    assert_eq!(step.errors[0], None);
+    assert_eq!(step.observations.len(), 3 * OBSERVATION_SIZE);
+
+    //the crashed instance starts over and the others carry on
+    let step = batch.step(&[0, 0, 1 << 1]).unwrap();
+    assert_eq!(step.done, vec![false, false, true]);
+    assert_eq!(step.errors[..2], [None, None]);
+    assert!(step.errors[2].is_some());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,43 @@
+use chip8::machine::{Machine, MAX_ROM_SIZE};
+use chip8::quirks::{FontSet, Quirks};
+
+const FONT_SETS: [FontSet; 4] = [FontSet::Vip, FontSet::Chip48, FontSet::Schip, FontSet::Octo];
+
+#[test]
+fn font_sets_differ() {
+    for (i, a) in FONT_SETS.iter().enumerate() {
+        for b in &FONT_SETS[i + 1..] {
+            assert_ne!(a.glyphs(), b.glyphs(), "{} and {} are the same font", a.name(), b.name());
+        }
+    }
+}
+
+#[test]
+fn octo_has_its_own_digits() {
+    let octo = FontSet::Octo.glyphs();
+    let schip = FontSet::Schip.glyphs();
+
+    assert_eq!(octo[4 * 5..5 * 5], [0xA0, 0xA0, 0xF0, 0x20, 0x20]);
+    assert_eq!(octo[0xb * 5..0xc * 5], [0xF0, 0x50, 0x70, 0x50, 0xF0]);
+    assert_eq!(octo[0xd * 5..0xe * 5], [0xF0, 0x50, 0x50, 0x50, 0xF0]);
+    //every other digit is SCHIP's
+    for digit in (0..16).filter(|digit| ![4, 0xb, 0xd].contains(digit)) {
+        assert_eq!(octo[digit * 5..digit * 5 + 5], schip[digit * 5..digit * 5 + 5], "digit {:x}", digit);
+    }
+}
+
+#[test]
+fn font_and_rom_must_fit() {
+    let mut quirks = Quirks::default();
+    assert!(Machine::new(&[0; MAX_ROM_SIZE], 0, &quirks).is_ok());
+    assert!(Machine::new(&[0; MAX_ROM_SIZE + 1], 0, &quirks).is_err());
+
+    quirks.font_address = 0x1b0;
+    let machine = Machine::new(&[], 0, &quirks).unwrap();
+    assert_eq!(machine.memory[0x1b0..0x200], quirks.font.glyphs());
+
+    quirks.font_address = 0x1c0;
+    assert!(Machine::new(&[], 0, &quirks).is_err());
+    quirks.font_address = 0xfff;
+    assert!(Machine::new(&[], 0, &quirks).is_err());
+}